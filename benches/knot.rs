@@ -0,0 +1,72 @@
+// Compares the in-place knot against the allocating implementation it
+// replaced by hashing the 128 rows of day 14. Run with `cargo bench`.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use y2017::knot::KnotHash;
+
+const SUFFIX: [u8; 5] = [17, 31, 73, 47, 23];
+const RUNS: u32 = 10;
+
+// The previous implementation, rebuilding the whole circle for every length.
+fn tick(v: &[u8], pos: usize, length: usize) -> Vec<u8> {
+    let v_len = v.len();
+
+    let rot = v.iter().cycle().skip(pos).take(length).collect::<Vec<_>>().into_iter().rev();
+    let copy = v.iter().cycle().skip(pos + length).take(v_len - length);
+
+    rot.chain(copy).cycle().skip(v_len - pos).take(v_len).cloned().collect::<Vec<_>>()
+}
+
+fn allocating(input: &[u8]) -> u128 {
+    let mut lengths = input.to_vec();
+    lengths.extend_from_slice(&SUFFIX);
+
+    let mut vec = (0..=255).collect::<Vec<u8>>();
+    let mut pos = 0;
+    let mut skip = 0;
+
+    for _ in 0..64 {
+        for l in &lengths {
+            let l = *l as usize;
+            vec = tick(&vec, pos, l);
+            pos = (pos + l + skip) % vec.len();
+            skip += 1;
+        }
+    }
+
+    let mut dense = [0; 16];
+    for (i, digit) in dense.iter_mut().enumerate() {
+        *digit = vec.iter().skip(i * 16).take(16).fold(0, |acc, v| acc ^ v);
+    }
+
+    u128::from_be_bytes(dense)
+}
+
+fn in_place(input: &[u8]) -> u128 {
+    KnotHash::new(input).to_bits()
+}
+
+fn measure(name: &str, keys: &[String], f: fn(&[u8]) -> u128) -> (Duration, u32) {
+    let mut used = 0;
+    let start = Instant::now();
+
+    for _ in 0..RUNS {
+        used = keys.iter().map(|k| black_box(f(black_box(k.as_bytes()))).count_ones()).sum();
+    }
+
+    let elapsed = start.elapsed() / RUNS;
+    println!("{:<12} {:>10.3?} per 128 hashes ({} used squares)", name, elapsed, used);
+
+    (elapsed, used)
+}
+
+fn main() {
+    let key = include_str!("../input/2017/day14.txt").trim();
+    let keys = (0..128).map(|y| format!("{}-{}", key, y)).collect::<Vec<_>>();
+
+    let (old, old_used) = measure("allocating", &keys, allocating);
+    let (new, new_used) = measure("in place", &keys, in_place);
+
+    assert_eq!(old_used, new_used);
+    println!("speedup      {:>10.1}x", old.as_secs_f64() / new.as_secs_f64());
+}
@@ -0,0 +1,82 @@
+// Dumps the image of every iteration of a day 21 enhancement book.
+//
+// usage: fractal [-n ITERATIONS] [-f pbm|png] [-o DIR] [--check] RULES
+//
+// With --check the rules are only validated.
+use std::path::PathBuf;
+use std::process;
+use y2017::fractal::{self, Format};
+
+fn usage(message: &str) -> ! {
+    eprintln!("fractal: {}", message);
+    eprintln!("usage: fractal [-n ITERATIONS] [-f pbm|png] [-o DIR] [--check] RULES");
+    process::exit(2);
+}
+
+fn main() {
+    let mut iterations = 5;
+    let mut format = Format::Pbm;
+    let mut dir = PathBuf::from(".");
+    let mut check = false;
+    let mut rules = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" | "--iterations" => {
+                iterations = match args.next().map(|n| n.parse()) {
+                    Some(Ok(n)) => n,
+                    _ => usage("missing or invalid number of iterations"),
+                };
+            },
+            "-f" | "--format" => {
+                format = match args.next().as_deref() {
+                    Some("pbm") => Format::Pbm,
+                    Some("png") => Format::Png,
+                    Some(f) => usage(&format!("unknown format '{}'", f)),
+                    None => usage("missing format"),
+                };
+            },
+            "-o" | "--output" => match args.next() {
+                Some(d) => dir = PathBuf::from(d),
+                None => usage("missing output directory"),
+            },
+            "--check" => check = true,
+            "-h" | "--help" => usage("images of all iterations of an enhancement book"),
+            _ if rules.is_none() => rules = Some(arg),
+            _ => usage(&format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let path = rules.unwrap_or_else(|| usage("missing rules"));
+    let input = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("fractal: {}: {}", path, e);
+        process::exit(1);
+    });
+
+    let result = fractal::parse(&input).and_then(|enhancements| {
+        if check {
+            let problems = fractal::validate(&enhancements);
+            for p in &problems {
+                println!("{}: {}", path, p);
+            }
+            if !problems.is_empty() {
+                process::exit(1);
+            }
+
+            return Ok(());
+        }
+
+        fractal::reject_malformed(&enhancements)?;
+        for file in fractal::dump(&enhancements, iterations, &dir, format)? {
+            println!("{}", file.display());
+        }
+
+        Ok(())
+    });
+
+    if let Err(e) = result {
+        eprintln!("fractal: {}: {}", path, e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,104 @@
+// Knot hashes of strings, files or stdin.
+//
+// usage: knothash [-f hex|binary|base64] [-i FILE]... [STRING]...
+//
+// Without strings or files stdin is hashed. A trailing newline of files
+// and stdin is ignored, like the puzzle input.
+use std::io::Read;
+use std::process;
+use y2017::knot::KnotHash;
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Hex,
+    Binary,
+    Base64,
+}
+
+impl Format {
+    fn render(&self, hash: &KnotHash) -> String {
+        match self {
+            Format::Hex => hash.to_hex(),
+            Format::Binary => hash.to_binary(),
+            Format::Base64 => hash.to_base64(),
+        }
+    }
+}
+
+enum Source {
+    Text(String),
+    File(String),
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("knothash: {}", message);
+    eprintln!("usage: knothash [-f hex|binary|base64] [-i FILE]... [STRING]...");
+    process::exit(2);
+}
+
+fn strip_newline(mut content: Vec<u8>) -> Vec<u8> {
+    if content.last() == Some(&b'\n') {
+        content.pop();
+        if content.last() == Some(&b'\r') {
+            content.pop();
+        }
+    }
+
+    content
+}
+
+fn main() {
+    let mut format = Format::Hex;
+    let mut sources = vec![];
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--format" => {
+                format = match args.next().as_deref() {
+                    Some("hex") => Format::Hex,
+                    Some("binary") => Format::Binary,
+                    Some("base64") => Format::Base64,
+                    Some(f) => usage(&format!("unknown format '{}'", f)),
+                    None => usage("missing format"),
+                };
+            },
+            "-i" | "--input" => match args.next() {
+                Some(path) => sources.push(Source::File(path)),
+                None => usage("missing file name"),
+            },
+            "-h" | "--help" => usage("knot hashes of strings, files or stdin"),
+            "--" => sources.extend(args.by_ref().map(Source::Text)),
+            _ => sources.push(Source::Text(arg)),
+        }
+    }
+
+    if sources.is_empty() {
+        let mut content = vec![];
+        if let Err(e) = std::io::stdin().read_to_end(&mut content) {
+            eprintln!("knothash: stdin: {}", e);
+            process::exit(1);
+        }
+
+        println!("{}", format.render(&KnotHash::new(&strip_newline(content))));
+        return;
+    }
+
+    let mut failed = false;
+    for source in sources {
+        match source {
+            Source::Text(text) => println!("{}  \"{}\"", format.render(&KnotHash::new(text.as_bytes())), text),
+            Source::File(path) => match std::fs::read(&path) {
+                Ok(content) => println!("{}  {}", format.render(&KnotHash::new(&strip_newline(content))), path),
+                Err(e) => {
+                    eprintln!("knothash: {}: {}", path, e);
+                    failed = true;
+                },
+            },
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,354 @@
+use std::str::FromStr;
+use crate::render::Scene;
+use crate::utils::grid::{Grid, Point};
+use crate::utils::ParseError;
+
+pub type Status = u8;
+
+pub const CLEAN: Status = 0;
+
+// Up, right, down, left, so turning right is the next direction.
+const DIRECTIONS: [Point; 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Turn {
+    Left,
+    Right,
+    Straight,
+    Reverse,
+}
+
+impl Turn {
+    // Quarter turns to the right.
+    fn quarters(&self) -> usize {
+        match self {
+            Turn::Straight => 0,
+            Turn::Right => 1,
+            Turn::Reverse => 2,
+            Turn::Left => 3,
+        }
+    }
+}
+
+impl FromStr for Turn {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        match s {
+            "L" => Ok(Turn::Left),
+            "R" => Ok(Turn::Right),
+            "N" => Ok(Turn::Straight),
+            "U" => Ok(Turn::Reverse),
+            _ => Err(ParseError::new(&format!("Unknown turn '{}'", s))),
+        }
+    }
+}
+
+// What happens to a node in a given status when the carrier bursts on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    pub turn: Turn,
+    pub next: Status,
+    pub infects: bool,
+}
+
+// The rules by status, status 0 is a clean node. Each status has a
+// symbol used to parse and print the grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+    pub symbols: Vec<char>,
+    pub rules: Vec<Rule>,
+}
+
+impl Rules {
+    // The rules of day 22 part 1.
+    pub fn simple() -> Self {
+        Rules::from_str(". # L *\n# . R").unwrap()
+    }
+
+    // The rules of day 22 part 2.
+    pub fn evolved() -> Self {
+        Rules::from_str(". W L\nW # N *\n# F R\nF . U").unwrap()
+    }
+
+    // Langton's ant and its variants, e.g. "RL" for the original ant.
+    // Every status turns as given and moves on to the next one. Leaving
+    // the clean status counts as an infection.
+    pub fn langton(turns: &str) -> Result<Self, ParseError> {
+        const SYMBOLS: &str = ".#123456789abcdefghijklmnopqrstuvwxyz";
+
+        let symbols = SYMBOLS.chars().take(turns.len()).collect::<Vec<_>>();
+        if symbols.len() < 2 || symbols.len() != turns.len() {
+            return Err(ParseError::new("Need between 2 and 37 turns"));
+        }
+
+        let rules = turns.chars()
+            .enumerate()
+            .map(|(i, t)| Ok(Rule {
+                turn: Turn::from_str(&t.to_string())?,
+                next: ((i + 1) % turns.len()) as Status,
+                infects: i == 0,
+            }))
+            .collect::<Result<Vec<_>, ParseError>>()?;
+
+        Ok(Rules { symbols, rules })
+    }
+
+    pub fn status(&self, c: char) -> Result<Status, ParseError> {
+        self.symbols.iter()
+            .position(|s| *s == c)
+            .map(|s| s as Status)
+            .ok_or(ParseError::new(&format!("Unknown node '{}'", c)))
+    }
+}
+
+// One rule per line: the symbol of a status, the symbol of the next
+// status, the turn (L, R, N or U) and a * if it counts as an infection.
+// The first line describes the clean status.
+impl FromStr for Rules {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let lines = s.lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>();
+
+        let symbol = |s: &str| {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(ParseError::new(&format!("Invalid symbol '{}'", s))),
+            }
+        };
+
+        let symbols = lines.iter()
+            .map(|l| symbol(l[0]))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut rules = Rules { symbols, rules: vec![] };
+        for l in &lines {
+            if l.len() < 3 || l.len() > 4 || (l.len() == 4 && l[3] != "*") {
+                return Err(ParseError::new(&format!("Could not parse rule '{}'", l.join(" "))));
+            }
+
+            let rule = Rule {
+                next: rules.status(symbol(l[1])?)?,
+                turn: Turn::from_str(l[2])?,
+                infects: l.len() == 4,
+            };
+            rules.rules.push(rule);
+        }
+
+        if rules.rules.is_empty() || rules.rules.len() > Status::MAX as usize + 1 {
+            return Err(ParseError::new("Invalid number of rules"));
+        }
+
+        Ok(rules)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Carrier {
+    pub pos: Point,
+    direction: usize,
+    pub nodes: Grid<Status>,
+    pub rules: Rules,
+    pub infected: usize,
+    pub bursts: usize,
+}
+
+impl Carrier {
+    // Nodes in `map` are given by the symbols of `rules`, the carrier
+    // starts in the middle facing up.
+    pub fn new(map: &Grid<char>, rules: Rules) -> Result<Self, ParseError> {
+        let mut nodes = Grid::dense(map.width(), map.height(), CLEAN);
+        for (p, c) in map.iter() {
+            nodes.set(p, rules.status(*c)?);
+        }
+
+        let offset = (-(map.width() as isize / 2), -(map.height() as isize / 2));
+        let nodes = nodes.with_origin(offset);
+
+        let pos = (0, 0);
+        let direction = 0;
+
+        Ok(Self { pos, direction, nodes, rules, infected: 0, bursts: 0 })
+    }
+
+    pub fn dir(&self) -> Point {
+        DIRECTIONS[self.direction]
+    }
+
+    pub fn burst(&mut self) {
+        let node = self.nodes.get_mut(self.pos);
+        let rule = self.rules.rules[*node as usize];
+        *node = rule.next;
+
+        if rule.infects {
+            self.infected += 1;
+        }
+
+        self.direction = (self.direction + rule.turn.quarters()) % 4;
+        let dir = DIRECTIONS[self.direction];
+        self.pos.0 += dir.0;
+        self.pos.1 += dir.1;
+        self.bursts += 1;
+    }
+
+    // Same as bursting `bursts` times, but works on the cells directly
+    // and only looks at the grid again when the carrier leaves it. The
+    // next status and direction are looked up by status * 4 + direction
+    // and packed the same way, moving is adding the offset of the
+    // direction to the cell index.
+    pub fn run(&mut self, bursts: usize) {
+        let mut moves = vec![];
+        let mut infects = vec![];
+        for rule in &self.rules.rules {
+            for d in 0..4 {
+                moves.push(rule.next as usize * 4 + (d + rule.turn.quarters()) % 4);
+                infects.push(rule.infects as usize);
+            }
+        }
+
+        let mut left = bursts;
+        while left > 0 {
+            self.nodes.grow(self.pos);
+            let (origin, width, cells) = match self.nodes.dense_mut() {
+                Some(dense) => dense,
+                None => {
+                    self.burst();
+                    left -= 1;
+                    continue;
+                },
+            };
+
+            let height = cells.len() / width;
+            let mut x = (self.pos.0 - origin.0) as usize;
+            let mut y = (self.pos.1 - origin.1) as usize;
+            let mut i = y * width + x;
+            let offsets = DIRECTIONS.map(|d| (d.1 * width as isize + d.0) as usize);
+            let mut direction = self.direction;
+            let mut infected = 0;
+            let mut steps = 0;
+
+            while steps < left && x < width && y < height {
+                let state = cells[i] as usize * 4 + direction;
+                let next = moves[state];
+
+                cells[i] = (next / 4) as Status;
+                direction = next % 4;
+                infected += infects[state];
+
+                let dir = DIRECTIONS[direction];
+                x = x.wrapping_add(dir.0 as usize);
+                y = y.wrapping_add(dir.1 as usize);
+                i = i.wrapping_add(offsets[direction]);
+                steps += 1;
+            }
+
+            left -= steps;
+            self.infected += infected;
+            self.bursts += steps;
+            self.direction = direction;
+            self.pos = (origin.0 + x as isize, origin.1 + y as isize);
+        }
+    }
+}
+
+impl Scene for Carrier {
+    fn cell(&self, p: Point) -> char {
+        self.rules.symbols[*self.nodes.get(p) as usize]
+    }
+
+    fn bounds(&self) -> Option<(Point, Point)> {
+        self.nodes.bounds()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "..#\n#..\n...";
+
+    #[test]
+    pub fn simple_and_evolved() {
+        let map = Grid::parse(EXAMPLE, '.', Ok).unwrap();
+
+        let mut simple = Carrier::new(&map, Rules::simple()).unwrap();
+        simple.run(70);
+        let mut evolved = Carrier::new(&map, Rules::evolved()).unwrap();
+        evolved.run(100);
+
+        assert_eq!(41, simple.infected);
+        assert_eq!(26, evolved.infected);
+        assert_eq!(100, evolved.bursts);
+    }
+
+    #[test]
+    pub fn run_matches_bursts() {
+        let map = Grid::parse(EXAMPLE, '.', Ok).unwrap();
+
+        for rules in [Rules::simple(), Rules::evolved(), Rules::langton("RLLR").unwrap()] {
+            let mut single = Carrier::new(&map, rules.clone()).unwrap();
+            let mut fast = Carrier::new(&map, rules).unwrap();
+            for _ in 0..5_000 {
+                single.burst();
+            }
+            fast.run(2_000);
+            fast.run(3_000);
+
+            assert_eq!((single.pos, single.dir()), (fast.pos, fast.dir()));
+            assert_eq!((single.infected, single.bursts), (fast.infected, fast.bursts));
+            assert_eq!(single.nodes.to_string(), fast.nodes.to_string());
+        }
+    }
+
+    #[test]
+    pub fn langton_ant() {
+        // the classic ant builds its highway after about 10000 steps
+        let empty = Grid::dense(1, 1, '.');
+        let ant = Rules::langton("RL").unwrap();
+
+        let mut carrier = Carrier::new(&empty, ant.clone()).unwrap();
+        carrier.run(11_000);
+        let (min, max) = carrier.nodes.bounds().unwrap();
+
+        assert!(max.0 - min.0 > 64 || max.1 - min.1 > 64);
+        assert_eq!(Rules::from_str(". # R *\n# . L").unwrap(), ant);
+    }
+
+    #[test]
+    pub fn render() {
+        use crate::render::{Animation, Marked, Viewport};
+
+        let map = Grid::parse(EXAMPLE, '.', Ok).unwrap();
+        let mut carrier = Carrier::new(&map, Rules::simple()).unwrap();
+        let mut animation = Animation::new(Viewport::around((0, 0), 9, 5), 7);
+
+        for step in 0..=70 {
+            animation.record(step, &Marked { scene: &carrier, at: carrier.pos, marker: '@' });
+            carrier.burst();
+        }
+
+        let expected = "\
+...#....#
+..#.#@..#
+..#.#..#.
+.....##..
+.........";
+
+        assert_eq!(11, animation.frames.len());
+        assert_eq!(expected, animation.frames[10].1.to_string());
+    }
+
+    #[test]
+    pub fn invalid_rules() {
+        assert!(Rules::from_str(". x L").is_err());
+        assert!(Rules::from_str(". . Q").is_err());
+        assert!(Rules::from_str(". . L +").is_err());
+        assert!(Rules::langton("RX").is_err());
+        assert!(Carrier::new(&Grid::parse("..x", '.', Ok).unwrap(), Rules::simple()).is_err());
+    }
+}
@@ -1,108 +1,122 @@
-use std::collections::HashSet;
-use std::collections::HashMap;
+use std::fmt;
+use std::thread;
 use crate::utils::ParseError;
-use crate::knot::hash;
-
-fn used(c: char) -> usize {
-    match c {
-        '0' => 0,
-        '1' => 1,
-        '2' => 1,
-        '3' => 2,
-        '4' => 1,
-        '5' => 2,
-        '6' => 2,
-        '7' => 3,
-        '8' => 1,
-        '9' => 2,
-        'a' => 2,
-        'b' => 3,
-        'c' => 2,
-        'd' => 3,
-        'e' => 3,
-        'f' => 4,
-        _ => 0,
-    }
-}
+use crate::knot::KnotHash;
+use crate::regions::label;
+use crate::utils::grid::Grid;
 
-#[aoc(day14, part1)]
-fn problem1(input: &str) -> Result<usize, ParseError> {
-    let result = (0..128)
-        .map(|v| format!("{}-{}", input, v))
-        .map(|v| hash(v.as_str()))
-        .map(|h| h.chars().map(|c| used(c)).sum::<usize>())
-        .sum::<usize>();
-
-    Ok(result)
-}
+const SIZE: usize = 128;
+const THREADS: usize = 8;
 
-type C = isize;
-type Coords = (C, C);
-
-fn to_map(h: Vec<String>) -> Result<HashMap<Coords, char>, ParseError> {
-    let v = h.into_iter()
-        .map(|s| s.chars().map(|c| c.to_digit(16)).collect::<Option<Vec<_>>>())
-        .collect::<Option<Vec<_>>>()
-        .ok_or(ParseError::new("Unable to parse digit"))?
-        .into_iter()
-        .map(|v| v.into_iter().map(|c| format!("{:04b}", c).chars().collect::<Vec<_>>()).flatten().collect::<Vec<_>>())
-        .collect::<Vec<_>>()
-        .into_iter()
-        .enumerate()
-        .map(move |(y, r)| r.into_iter().enumerate().map(move |(x, v)| ((x as isize, y as isize), v)))
-        .flatten()
-        .collect::<HashMap<_, _>>();
-
-    Ok(v)
+// The disk as a bitset, one row per u128 with x = 0 as the most
+// significant bit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskGrid {
+    rows: [u128; SIZE],
 }
 
-#[aoc(day14, part2)]
-fn problem2(input: &str) -> Result<usize, ParseError> {
-    let result = (0..128)
-        .map(|v| format!("{}-{}", input, v))
-        .map(|v| hash(v.as_str()))
-        .collect::<Vec<_>>();
-
-    let mut map = to_map(result)?;
-    let mut regions = vec![];
-    let delta = vec![-1, 1];
-
-    while let Some(next) = map.iter().filter(|(_, v)| **v == '1').map(|(k, _)| k).next() {
-        let mut queue = vec![*next];
-        let mut visited = HashSet::new();
-
-        while let Some(q) = queue.pop() {
-            if visited.contains(&q) {
-                continue;
-            }
+impl DiskGrid {
+    // Hashes the rows in parallel, each thread takes every THREADS-th row.
+    pub fn new(key: &str) -> Self {
+        let key = key.trim();
+        let mut rows = [0; SIZE];
+
+        thread::scope(|s| {
+            let handles = (0..THREADS)
+                .map(|t| s.spawn(move || {
+                    (t..SIZE).step_by(THREADS)
+                        .map(|y| (y, row(key, y)))
+                        .collect::<Vec<_>>()
+                }))
+                .collect::<Vec<_>>();
 
-            visited.insert(q);
-            for dx in &delta {
-                let x = q.0 + dx;
-                let y = q.1;
-                if let Some(v) = map.get(&(x, y)) {
-                    if *v == '1' {
-                        queue.push((x, y));
-                    }
+            for handle in handles {
+                for (y, r) in handle.join().unwrap() {
+                    rows[y] = r;
                 }
             }
+        });
 
-            for dy in &delta {
-                let x = q.0;
-                let y = q.1 + dy;
-                if let Some(v) = map.get(&(x, y)) {
-                    if *v == '1' {
-                        queue.push((x, y));
-                    }
-                }
+        DiskGrid { rows }
+    }
+
+    pub fn is_used(&self, x: usize, y: usize) -> bool {
+        self.rows[y] >> (SIZE - 1 - x) & 1 == 1
+    }
+
+    pub fn used(&self) -> usize {
+        self.rows.iter().map(|r| r.count_ones() as usize).sum()
+    }
+
+    // Used squares as '#', free ones as '.'.
+    pub fn to_grid(&self) -> Grid<char> {
+        let mut grid = Grid::dense(SIZE, SIZE, '.');
+        for y in 0..SIZE {
+            for x in (0..SIZE).filter(|x| self.is_used(*x, y)) {
+                grid.set((x as isize, y as isize), '#');
             }
         }
 
-        for v in &visited {
-            map.entry(*v).and_modify(|c| *c = '0');
-        }
-        regions.push(visited);
+        grid
+    }
+}
+
+impl fmt::Display for DiskGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_grid())
     }
+}
+
+fn row(key: &str, y: usize) -> u128 {
+    KnotHash::new(format!("{}-{}", key, y).as_bytes()).to_bits()
+}
+
+#[aoc_generator(day14)]
+fn get_input(input: &str) -> Result<DiskGrid, ParseError> {
+    Ok(DiskGrid::new(input))
+}
+
+#[aoc(day14, part1)]
+fn problem1(input: &DiskGrid) -> Result<usize, ParseError> {
+    Ok(input.used())
+}
+
+#[aoc(day14, part2)]
+fn problem2(input: &DiskGrid) -> Result<usize, ParseError> {
+    let regions = label(SIZE, SIZE, |x, y| input.is_used(x, y)).regions;
 
     Ok(regions.len())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn example() {
+        let disk = DiskGrid::new("flqrgnkx");
+
+        assert!(disk.is_used(0, 0));
+        assert!(!disk.is_used(2, 0));
+        assert!(disk.is_used(1, 1));
+        assert_eq!(8108, problem1(&disk).unwrap());
+        assert_eq!(1242, problem2(&disk).unwrap());
+    }
+
+    #[test]
+    pub fn example_corner() {
+        let disk = DiskGrid::new("flqrgnkx");
+        let expected = "\
+##.#.#..
+.#.#.#.#
+....#.#.
+#.#.##.#
+.##.#...
+##..#..#
+.#...#..
+##.#.##.";
+
+        assert_eq!(expected, disk.to_grid().sub((0, 0), 8, 8).to_string());
+        assert_eq!(SIZE * SIZE + SIZE - 1, disk.to_string().len());
+    }
+}
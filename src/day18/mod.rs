@@ -1,269 +1,71 @@
-use std::collections::HashMap;
-use std::str::FromStr;
-use crate::utils::ParseError;
-
-fn first(s: &str) -> Result<char, ParseError> {
-    s.chars().next().ok_or(ParseError::new("Cannot parse empty string"))
-}
-
-#[derive(Debug, Clone)]
-enum Param {
-    Register(char),
-    Value(isize),
-}
-
-impl FromStr for Param {
-    type Err = ParseError;
-
-    fn from_str(s: &str) -> Result<Self, ParseError> {
-        use Param::*;
-
-        let first = first(s)?;
-        if s.len() == 1 && first.is_alphabetic() {
-            return Ok(Register(first));
-        }
-
-        Ok(Value(s.parse::<isize>()?))
-    }
-}
-
-#[derive(Debug, Clone)]
-enum Instruction {
-    Snd(char),
-    Set((char, Param)),
-    Add((char, Param)),
-    Mul((char, Param)),
-    Mod((char, Param)),
-    Rcv(char),
-    Jgz((Param, Param)),
-}
-
-impl FromStr for Instruction {
-    type Err = ParseError;
-
-    fn from_str(s: &str) -> Result<Self, ParseError> {
-        use Instruction::*;
-
-        let i = s.split(" ").collect::<Vec<_>>();
-
-        if i.len() < 2 || i.len() > 3 {
-            return Err(ParseError::new("Invalid input"));
-        }
-
-        Ok(match i[0] {
-            "snd" => Snd(first(i[1])?),
-            "set" => Set((first(i[1])?, Param::from_str(i[2])?)),
-            "add" => Add((first(i[1])?, Param::from_str(i[2])?)),
-            "mul" => Mul((first(i[1])?, Param::from_str(i[2])?)),
-            "mod" => Mod((first(i[1])?, Param::from_str(i[2])?)),
-            "rcv" => Rcv(first(i[1])?),
-            "jgz" => Jgz((Param::from_str(i[1])?, Param::from_str(i[2])?)),
-            _ => Err(ParseError::new("Invalid instruction"))?
-        })
-    }
-}
+use crate::utils::{Budget, ParseError};
+use crate::vm::{self, Instruction, Io, Machine, Receive};
+use crate::vm::scheduler::Network;
 
 #[aoc_generator(day18)]
 fn get_input(input: &str) -> Result<Vec<Instruction>, ParseError> {
-    input
-        .lines()
-        .map(|l| Instruction::from_str(l))
-        .collect::<Result<Vec<_>, ParseError>>()
+    vm::parse(input)
 }
 
-#[derive(Debug)]
-struct Duet {
-    instructions: Vec<Instruction>,
-    registers: HashMap<char, isize>,
+// Part 1: `snd` plays a sound, `rcv` recovers the last sound played if
+// its register is non-zero.
+#[derive(Debug, Default)]
+struct Sound {
     frequency: Option<isize>,
-    v2: bool,
-    machine: isize,
-    sent: usize,
-    ip: usize,
+    recovered: Option<isize>,
 }
 
-impl Duet {
-    fn new(instructions: &Vec<Instruction>) -> Duet {
-        let instructions = instructions.clone();
-        let registers = HashMap::new();
-        let frequency = None;
-        let sent = 0;
-        let machine = 0;
-        let ip = 0;
-
-        Duet { instructions, registers, frequency, v2: false, machine, sent, ip }
-    }
-
-    fn v2(instructions: &Vec<Instruction>, machine: isize) -> Self {
-        let mut duet = Duet::new(instructions);
-
-        duet.machine = machine;
-        duet.v2 = true;
-        duet.registers.entry('p').or_insert(machine);
-
-        duet
-    }
-
-    fn resolve(&self, p: Param) -> isize {
-        use Param::*;
-
-        match p {
-            Register(r) => *self.registers.get(&r).unwrap_or(&0),
-            Value(v) => v,
-        }
-    }
-
-    fn snd(&mut self, register: char) {
-        let value = self.registers.get(&register).unwrap_or(&0);
-
-        if self.v2 {
-            return;
-        }
-
-        self.frequency = Some(*value);
+impl Io for Sound {
+    fn snd(&mut self, value: isize) -> bool {
+        self.frequency = Some(value);
+        true
     }
 
-    fn set(&mut self, a: char, p: Param) {
-        let v = self.resolve(p);
-        self.registers.entry(a).and_modify(|r| *r = v).or_insert(v);
-    }
-
-    fn add(&mut self, a: char, p: Param) {
-        let v = self.resolve(p);
-        self.registers.entry(a).and_modify(|r| *r += v).or_insert(v);
-    }
-
-    fn mul(&mut self, a: char, p: Param) {
-        let v = self.resolve(p);
-        self.registers.entry(a).and_modify(|r| *r *= v).or_insert(0);
-    }
-
-    fn mod_(&mut self, a: char, p: Param) {
-        let v = self.resolve(p);
-        self.registers.entry(a).and_modify(|r| *r = *r % v).or_insert(0);
-    }
-
-    fn rcv(&mut self, a: char) {
-        if self.v2 {
-            return
-        }
-
-        let v = *self.registers.get(&a).unwrap_or(&0);
-
-        if v == 0 {
-            return;
+    fn rcv(&mut self, current: isize) -> Receive {
+        if current == 0 || self.frequency.is_none() {
+            return Receive::Skip;
         }
 
-        if let Some(f) = self.frequency {
-            self.registers.entry(a).and_modify(|r| *r = f);
-        }
+        self.recovered = self.frequency;
+        Receive::Stop
     }
+}
 
-    fn jgz(&self, a: Param, b: Param) -> isize {
-        let v = self.resolve(a);
-
-        if v > 0 {
-            self.resolve(b)
-        } else {
-            1
-        }
-    }
-
-    fn run(&mut self) -> Option<isize> {
-        use Instruction::*;
-
-        let mut i = 0;
-        let instructions = self.instructions.clone();
-        while i < self.instructions.len() {
-            let instr = &instructions[i];
-
-            if !self.v2 {
-                if let Rcv(r) = instr {
-                    let v = *self.registers.get(&r).unwrap_or(&0);
-                    if v != 0 && self.frequency.is_some() {
-                        break;
-                    }
-                }
-            }
-
-            match instr {
-                Snd(f) => self.snd(*f),
-                Set((a, p)) => self.set(*a, p.clone()),
-                Add((a, p)) => self.add(*a, p.clone()),
-                Mul((a, p)) => self.mul(*a, p.clone()),
-                Mod((a, p)) => self.mod_(*a, p.clone()),
-                Rcv(a) => self.rcv(*a),
-                Jgz((a, p)) => i = ((i as isize) + self.jgz(a.clone(), p.clone()) - 1) as usize,
-            }
+const BUDGET: usize = 10_000_000;
 
-            i += 1;
-        }
-
-        self.frequency
-    }
+#[aoc(day18, part1)]
+fn problem1(input: &[Instruction]) -> Result<isize, ParseError> {
+    let mut duet = Machine::new(input, Sound::default());
 
-    fn step(&mut self, buffer_in: Vec<isize>) -> Vec<isize> {
-        use Instruction::*;
+    duet.run_within(&mut Budget::new(BUDGET))?;
+    duet.io.recovered.ok_or(ParseError::new("Could not find frequency"))
+}
 
-        let mut buffer_in = buffer_in.into_iter().rev().collect::<Vec<_>>();
-        let mut buffer_out = Vec::with_capacity(500_000_000);
-        let mut i = self.ip;
-        let instructions = self.instructions.clone();
-        while i < self.instructions.len() {
-            let instr = &instructions[i];
+#[aoc(day18, part2)]
+fn problem2(input: &[Instruction]) -> Result<usize, ParseError> {
+    let mut duets = Network::ring(input, 2, None);
 
-            match instr {
-                Snd(f) => {
-                    self.sent += 1;
-                    let v = self.registers.get(f).unwrap_or(&0);
-                    buffer_out.push(*v);
-                },
-                Set((a, p)) => self.set(*a, p.clone()),
-                Add((a, p)) => self.add(*a, p.clone()),
-                Mul((a, p)) => self.mul(*a, p.clone()),
-                Mod((a, p)) => self.mod_(*a, p.clone()),
-                Rcv(a) => {
-                    if let Some(value) = buffer_in.pop() {
-                        self.registers.entry(*a).and_modify(|v| *v = value).or_insert(value);
-                    } else {
-                        self.ip = i;
-                        return buffer_out;
-                    }
-                }
-                Jgz((a, b)) => i = ((i as isize) + self.jgz(a.clone(), b.clone()) - 1) as usize,
-            }
+    // The programs usually end up waiting for each other.
+    duets.run_within(&mut Budget::new(BUDGET))?;
 
-            i += 1;
-        }
-
-        self.ip = i;
-        buffer_out
-    }
+    Ok(duets.programs[1].io.sent)
 }
 
-#[aoc(day18, part1)]
-fn problem1(input: &Vec<Instruction>) -> Result<isize, ParseError> {
-    let mut duet = Duet::new(input);
-    duet.run().ok_or(ParseError::new("Could not find frequency"))
-}
+#[cfg(test)]
+mod test {
+    use super::*;
 
-#[aoc(day18, part2)]
-fn problem2(input: &Vec<Instruction>) -> Result<usize, ParseError> {
-    let mut duets = [Duet::v2(input, 0), Duet::v2(input, 1)];
-    let mut current = 0;
-    let mut buffer = vec![];
-    let mut last_out = [10, 10];
+    #[test]
+    pub fn example_1_1() {
+        let input = get_input(include_str!("example")).unwrap();
 
-    loop {
-        buffer = duets[current].step(buffer);
+        assert_eq!(4, problem1(&input).unwrap());
+    }
 
-        last_out[current] = buffer.len();
-        current = (current + 1) % 2;
+    #[test]
+    pub fn example_2_1() {
+        let input = get_input("snd 1\nsnd 2\nsnd p\nrcv a\nrcv b\nrcv c\nrcv d").unwrap();
 
-        if last_out[0] == 0 && last_out[1] == 0 {
-            break;
-        }
+        assert_eq!(3, problem2(&input).unwrap());
     }
-
-    Ok(duets[1].sent)
 }
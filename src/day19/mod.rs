@@ -1,136 +1,231 @@
-use std::str::FromStr;
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
 use crate::utils::ParseError;
+use crate::utils::grid::{Grid, Point};
+
+type Coords = Point;
+type Map = Grid<char>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TraceError {
+    InvalidCell(Coords, char),
+    NoStart,
+    AmbiguousStart(Vec<Coords>),
+    // A junction with no or more than one way to turn.
+    AmbiguousJunction(Coords),
+    DeadEnd(Coords),
+    // The path reached the same position in the same direction again.
+    Loop(Coords),
+}
 
-type C = isize;
-type Coords = (C, C);
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceError::InvalidCell(p, c) => write!(f, "Invalid character '{}' at {:?}", c, p),
+            TraceError::NoStart => write!(f, "Could not find start"),
+            TraceError::AmbiguousStart(starts) => write!(f, "Found several starts: {:?}", starts),
+            TraceError::AmbiguousJunction(p) => write!(f, "Could not determine direction at {:?}", p),
+            TraceError::DeadEnd(p) => write!(f, "Junction at {:?} leads nowhere", p),
+            TraceError::Loop(p) => write!(f, "Path loops at {:?}", p),
+        }
+    }
+}
 
-#[derive(Debug)]
-struct Map {
-    data: HashMap<Coords, char>,
-    size: Coords,
+impl From<TraceError> for ParseError {
+    fn from(error: TraceError) -> Self {
+        ParseError::new(&error.to_string())
+    }
 }
 
-impl FromStr for Map {
-    type Err = ParseError;
-
-    fn from_str(s: &str) -> Result<Map, ParseError> {
-        let data = s
-            .lines()
-            .enumerate()
-            .map(move |(y, l)| l
-                .chars()
-                .enumerate()
-                .map(move |(x, c)| ((x as isize, y as isize), c))
-            )
-            .flatten()
-            .collect::<HashMap<_, _>>();
-
-        let mx = data.iter().map(|(k, _)| k.0 as isize).max().ok_or(ParseError::new("Could not determine size in x direction"))?;
-        let my = data.iter().map(|(k, _)| k.1 as isize).max().ok_or(ParseError::new("Could not determine size in y direction"))?;
-        let size = (mx, my);
-
-        Ok(Map { data, size })
+fn validate(map: &Map) -> Result<(), TraceError> {
+    match map.iter().find(|(_, c)| !matches!(c, ' ' | '|' | '-' | '+' | 'A'..='Z')) {
+        Some((p, c)) => Err(TraceError::InvalidCell(p, *c)),
+        None => Ok(()),
     }
 }
 
 #[aoc_generator(day19)]
 fn get_input(input: &str) -> Result<Map, ParseError> {
-    Map::from_str(input)
-}
-
-fn find_start(map: &Map) -> Result<Coords, ParseError> {
-    let my = map.size.1;
-    let start = map.data.iter()
-        .filter(|(k, v)| (k.1 == my || k.1 == 0) && **v == '|')
-        .map(|(k, _)| k)
-        .next().ok_or(ParseError::new("Could not find start"))?;
+    let map = Grid::parse(input, ' ', Ok)?;
+    validate(&map)?;
 
-    Ok(*start)
+    Ok(map)
 }
 
-fn neighbors(map: &Map, c: &Coords) -> Vec<Coords> {
-    let delta = [-1, 1];
-
-    let mut n = vec![];
-    for d in &delta {
-        if let Some(v) = map.data.get(&(d + c.0, c.1)) {
-            if *v != ' ' {
-                n.push((d + c.0, c.1));
-            }
-        }
+fn is_path(map: &Map, c: Coords) -> bool {
+    *map.get(c) != ' '
+}
 
-        if let Some(v) = map.data.get(&(c.0, d + c.1)) {
-            if *v != ' ' {
-                n.push((c.0, d + c.1));
-            }
-        }
+// The path enters the diagram on one of its edges: a line that would
+// continue outside of the diagram and leads inside.
+fn find_start(map: &Map) -> Result<(Coords, Coords), TraceError> {
+    let starts = map.iter()
+        .flat_map(|(p, c)| {
+            let dirs: &[Coords] = match c {
+                '|' => &[(0, 1), (0, -1)],
+                '-' => &[(1, 0), (-1, 0)],
+                _ => &[],
+            };
+
+            dirs.iter()
+                .filter(move |d| !map.contains((p.0 - d.0, p.1 - d.1)) && is_path(map, (p.0 + d.0, p.1 + d.1)))
+                .map(move |d| (p, *d))
+        })
+        .collect::<Vec<_>>();
+
+    match starts.len() {
+        0 => Err(TraceError::NoStart),
+        1 => Ok(starts[0]),
+        _ => Err(TraceError::AmbiguousStart(starts.into_iter().map(|(p, _)| p).collect())),
     }
+}
 
-    n
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Trace {
+    // Every visited position in order, crossings are visited twice.
+    path: Vec<Coords>,
+    letters: Vec<(Coords, char)>,
 }
 
-fn is_path(map: &Map, c: &Coords) -> bool {
-    if let Some(v) = map.data.get(c) {
-        *v != ' '
-    } else {
-        false
+impl Trace {
+    fn word(&self) -> String {
+        self.letters.iter().map(|(_, c)| *c).collect()
+    }
+
+    fn steps(&self) -> usize {
+        self.path.len()
     }
 }
 
-fn trace(map: &Map, start: Coords) -> Result<(Vec<char>, usize), ParseError> {
-    let n = neighbors(map, &start);
+// Follows the path from `start` in direction `dir` until it ends. Only
+// junctions change the direction. `visit` is called with the step and
+// the position for every step.
+fn trace(map: &Map, start: Coords, mut dir: Coords, visit: &mut dyn FnMut(usize, Coords)) -> Result<Trace, TraceError> {
+    let mut seen = HashSet::new();
+    let mut result = Trace { path: vec![], letters: vec![] };
+    let mut pos = start;
+
+    loop {
+        if !seen.insert((pos, dir)) {
+            return Err(TraceError::Loop(pos));
+        }
 
-    if n.len() != 1 {
-        Err(ParseError::new("Could not determine start direction"))?;
-    }
+        visit(result.path.len(), pos);
+        result.path.push(pos);
 
-    let second = n[0];
-    let mut dir = (second.0 - start.0, second.1 - start.1);
-
-    let mut i = 0;
-    let mut path = vec![];
-    let mut prev = start.clone();
-    let mut queue = vec![start];
-    while let Some(q) = queue.pop() {
-        i += 1;
-        if let Some(v) = map.data.get(&q) {
-            if v.is_alphabetic() {
-                path.push(*v);
-            }
+        let c = *map.get(pos);
+        if c.is_ascii_uppercase() {
+            result.letters.push((pos, c));
         }
 
-        let next = (q.0 + dir.0, q.1 + dir.1);
-        if is_path(map, &next) {
-            queue.push(next);
-            prev = q;
-            continue;
+        if c == '+' {
+            let turns = [(dir.1, -dir.0), (-dir.1, dir.0)].into_iter()
+                .filter(|d| is_path(map, (pos.0 + d.0, pos.1 + d.1)))
+                .collect::<Vec<_>>();
+
+            dir = match turns.len() {
+                0 => return Err(TraceError::DeadEnd(pos)),
+                1 => turns[0],
+                _ => return Err(TraceError::AmbiguousJunction(pos)),
+            };
         }
 
-        if let Some(n) = neighbors(map, &q).into_iter().filter(|c| *c != prev).next() {
-            dir = (n.0 - q.0, n.1 - q.1);
-            queue.push(n);
-        } else {
-            break;
+        let next = (pos.0 + dir.0, pos.1 + dir.1);
+        if !is_path(map, next) {
+            return Ok(result);
         }
-        prev = q;
+        pos = next;
     }
+}
 
-    Ok((path, i))
+fn follow(map: &Map) -> Result<Trace, TraceError> {
+    let (start, dir) = find_start(map)?;
+    trace(map, start, dir, &mut |_, _| ())
 }
 
 #[aoc(day19, part1)]
 fn problem1(input: &Map) -> Result<String, ParseError> {
-    let start = find_start(input)?;
-    let (path, _) = trace(input, start)?;
-
-    Ok(path.into_iter().collect::<String>())
+    Ok(follow(input)?.word())
 }
 
 #[aoc(day19, part2)]
 fn problem2(input: &Map) -> Result<usize, ParseError> {
-    let start = find_start(input)?;
-    let (_, steps) = trace(input, start)?;
+    Ok(follow(input)?.steps())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::{Animation, Marked, Viewport};
+
+    #[test]
+    pub fn example() {
+        let map = get_input(include_str!("example")).unwrap();
 
-    Ok(steps)
+        assert_eq!("ABCDEF", problem1(&map).unwrap());
+        assert_eq!(38, problem2(&map).unwrap());
+    }
+
+    #[test]
+    pub fn path_with_coordinates() {
+        let map = get_input(include_str!("example")).unwrap();
+        let trace = follow(&map).unwrap();
+
+        assert_eq!((5, 0), trace.path[0]);
+        assert_eq!((1, 3), *trace.path.last().unwrap());
+        assert_eq!(((5, 2), 'A'), trace.letters[0]);
+        assert_eq!(2, trace.path.iter().filter(|p| **p == (5, 3)).count());
+    }
+
+    #[test]
+    pub fn starts_on_any_edge() {
+        let map = get_input("   \n--+\n  A").unwrap();
+
+        assert_eq!(Ok(((0, 1), (1, 0))), find_start(&map));
+        assert_eq!("A", problem1(&map).unwrap());
+        assert_eq!(4, problem2(&map).unwrap());
+
+        let map = get_input("  \n-A").unwrap();
+        assert_eq!(Ok(((0, 1), (1, 0))), find_start(&map));
+    }
+
+    #[test]
+    pub fn errors() {
+        assert_eq!(Err(TraceError::InvalidCell((1, 0), 'x')), validate(&Grid::parse("|x", ' ', Ok).unwrap()));
+        assert_eq!(Err(TraceError::NoStart), follow(&get_input(" A \n").unwrap()));
+        assert_eq!(Err(TraceError::AmbiguousStart(vec![(0, 0), (2, 0)])), follow(&get_input("| |\n| |\n   ").unwrap()));
+        assert_eq!(Err(TraceError::AmbiguousJunction((1, 1))), follow(&get_input(" | \n+++").unwrap()));
+        assert_eq!(Err(TraceError::DeadEnd((1, 1))), follow(&get_input(" |\n +").unwrap()));
+
+        let looping = [
+            " |   ",
+            " +--+",
+            " |  |",
+            " +--+",
+        ];
+        assert_eq!(Err(TraceError::Loop((2, 1))), follow(&get_input(&looping.join("\n")).unwrap()));
+    }
+
+    #[test]
+    pub fn animate_example() {
+        let map = get_input(include_str!("example")).unwrap();
+        let (start, dir) = find_start(&map).unwrap();
+        let mut animation = Animation::new(Viewport::fit(&map), 10);
+
+        trace(&map, start, dir, &mut |step, pos| {
+            animation.record(step, &Marked { scene: &map, at: pos, marker: '@' })
+        }).unwrap();
+
+        let expected = [
+            "     |          ",
+            "     |  +--+    ",
+            "     A  |  C    ",
+            " F---|--@-E|--+ ",
+            "     |  |  |  D ",
+            "     +B-+  +--+ ",
+        ];
+
+        assert_eq!(4, animation.frames.len());
+        assert_eq!(expected.join("\n"), animation.frames[1].1.to_string());
+    }
 }
@@ -1,117 +1,52 @@
-use std::collections::HashMap;
+use std::str::FromStr;
 use crate::utils::ParseError;
+use crate::turing::{Blueprint, Turing};
 
-enum State {
-    A,
-    B,
-    C,
-    D,
-    E,
-    F,
+#[aoc_generator(day25)]
+fn get_input(input: &str) -> Result<Blueprint, ParseError> {
+    Blueprint::from_str(input)
 }
 
-struct Turing {
-    state: State,
-    band: HashMap<usize, u8>,
-    pos: usize,
-}
+#[aoc(day25, part1)]
+fn problem1(input: &Blueprint) -> Result<usize, ParseError> {
+    let mut turing = Turing::new(input);
 
-impl Turing {
-    fn new() -> Self {
-        let state = State::A;
-        let band = HashMap::new();
-        let pos = 0;
+    turing.run(input.steps, true);
 
-        Self { state, band, pos }
-    }
-
-    fn write(&mut self, val: u8) {
-        self.band.entry(self.pos).and_modify(|v| *v = val).or_insert(val);
-    }
-
-    fn tick(&mut self) {
-        use State::*;
+    Ok(turing.checksum())
+}
 
-        let current = self.band.get(&self.pos).unwrap_or(&0);
-        match (&self.state, current) {
-            (A, 0) => {
-                self.write(1);
-                self.pos += 1;
-                self.state = B;
-            },
-            (A, 1) => {
-                self.write(0);
-                self.pos -= 1;
-                self.state = B;
-            },
-            (B, 0) => {
-                self.write(1);
-                self.pos -= 1;
-                self.state = C;
-            },
-            (B, 1) => {
-                self.write(0);
-                self.pos += 1;
-                self.state = E;
-            },
-            (C, 0) => {
-                self.write(1);
-                self.pos += 1;
-                self.state = E;
-            },
-            (C, 1) => {
-                self.write(0);
-                self.pos -= 1;
-                self.state = D;
-            },
-            (D, 0) => {
-                self.write(1);
-                self.pos -= 1;
-                self.state = A;
-            },
-            (D, 1) => {
-                self.write(1);
-                self.pos -= 1;
-                self.state = A;
-            },
-            (E, 0) => {
-                self.write(0);
-                self.pos += 1;
-                self.state = A;
-            },
-            (E, 1) => {
-                self.write(0);
-                self.pos += 1;
-                self.state = F;
-            },
-            (F, 0) => {
-                self.write(1);
-                self.pos += 1;
-                self.state = E;
-            },
-            (F, 1) => {
-                self.write(1);
-                self.pos += 1;
-                self.state = A;
-            },
-            _ => panic!("Cannot happen"),
-        }
-    }
+#[aoc(day25, part2)]
+fn problem2(_: &Blueprint) -> Result<usize, ParseError> {
+    Ok(0)
 }
 
-#[aoc(day25, part1)]
-fn problem1(_: &str) -> Result<usize, ParseError> {
-    let mut turing = Turing::new();
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::turing::{Action, Move};
 
-    for _ in 0..12683008 {
-        turing.tick();
-    }
+    #[test]
+    pub fn example_1_1() {
+        let blueprint = get_input(include_str!("example")).unwrap();
 
-    let checksum = turing.band.iter().map(|(_, v)| *v as usize).sum();
-    Ok(checksum)
-}
+        assert_eq!(3, problem1(&blueprint).unwrap());
+    }
 
-#[aoc(day25, part2)]
-fn problem2(_: &str) -> Result<usize, ParseError> {
-    Ok(0)
+    #[test]
+    pub fn checksum_when_turning_back_into_written_tape() {
+        // A writes a one and steps left, B sweeps right over blanks and
+        // halts on the one
+        let blueprint = Blueprint::new(0, 100, vec![
+            [Action::new(1, Move::Left, Some(1)), Action::new(1, Move::Left, Some(1))],
+            [Action::new(0, Move::Right, Some(1)), Action::new(0, Move::Right, None)],
+        ]);
+
+        let mut single = Turing::new(&blueprint);
+        single.run(blueprint.steps, false);
+
+        assert!(single.halted());
+        assert_eq!(0, single.checksum());
+        assert_eq!(single.checksum(), problem1(&blueprint).unwrap());
+    }
 }
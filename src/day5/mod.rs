@@ -1,5 +1,5 @@
 use std::num::ParseIntError;
-use crate::utils::ParseError;
+use crate::utils::{Budget, Exhausted, ParseError};
 
 #[aoc_generator(day5)]
 fn get_input(input: &str) -> Result<Vec<i32>, ParseError> {
@@ -37,15 +37,19 @@ fn jump<F>(pos: usize, jumps: &mut Vec<i32>, update: F) -> Option<usize>
     }
 }
 
-#[aoc(day5, part1)]
-pub fn problem1(input: &Vec<i32>) -> Result<i32, ParseError> {
-    let mut input = input.clone();
+// Follows the jumps until they lead outside of the list and returns the
+// number of steps. The error carries the position and the jumps.
+fn run<F>(jumps: &[i32], update: F, budget: &mut Budget) -> Result<i32, Exhausted<(usize, Vec<i32>)>>
+    where F: Fn(i32) -> i32 {
+
+    let mut jumps = jumps.to_vec();
     let mut counter = 0;
     let mut pos = 0;
 
     loop {
+        budget.spend(|| (pos, jumps.clone()))?;
         counter += 1;
-        let next = jump(pos, &mut input, inc);
+        let next = jump(pos, &mut jumps, &update);
 
         pos = if let Some(next) = next {
             next
@@ -57,6 +61,13 @@ pub fn problem1(input: &Vec<i32>) -> Result<i32, ParseError> {
     Ok(counter)
 }
 
+const BUDGET: usize = 100_000_000;
+
+#[aoc(day5, part1)]
+pub fn problem1(input: &[i32]) -> Result<i32, ParseError> {
+    Ok(run(input, inc, &mut Budget::new(BUDGET))?)
+}
+
 fn update_part_2(v: i32) -> i32 {
     if v >= 3 {
         v - 1
@@ -66,23 +77,8 @@ fn update_part_2(v: i32) -> i32 {
 }
 
 #[aoc(day5, part2)]
-pub fn problem2(input: &Vec<i32>) -> Result<i32, ParseError> {
-    let mut input = input.clone();
-    let mut counter = 0;
-    let mut pos = 0;
-
-    loop {
-        counter += 1;
-        let next = jump(pos, &mut input, update_part_2);
-
-        pos = if let Some(next) = next {
-            next
-        } else {
-            break;
-        };
-    }
-
-    Ok(counter)
+pub fn problem2(input: &[i32]) -> Result<i32, ParseError> {
+    Ok(run(input, update_part_2, &mut Budget::new(BUDGET))?)
 }
 
 #[cfg(test)]
@@ -128,4 +124,28 @@ mod test {
         assert_eq!(Some(4), next);
         assert_eq!(vec![2, 2, 0, 1, -3], j);
     }
+
+    #[test]
+    pub fn example_1_4() {
+        let j = vec![0, 3, 0, 1, -3];
+
+        assert_eq!(Ok(5), run(&j, inc, &mut Budget::unlimited()));
+    }
+
+    #[test]
+    pub fn example_2_2() {
+        let j = vec![0, 3, 0, 1, -3];
+
+        assert_eq!(Ok(10), run(&j, update_part_2, &mut Budget::unlimited()));
+    }
+
+    #[test]
+    pub fn budget_exhausted() {
+        let j = vec![0, 3, 0, 1, -3];
+
+        let exhausted = run(&j, inc, &mut Budget::new(2)).unwrap_err();
+
+        assert_eq!(2, exhausted.steps);
+        assert_eq!((1, vec![2, 3, 0, 1, -3]), exhausted.state);
+    }
 }
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use regex::Regex;
 use std::str::FromStr;
-use crate::utils::ParseError;
+use crate::utils::{Budget, Exhausted, ParseError};
 
 #[derive(Debug)]
 enum OperationType {
@@ -151,28 +151,42 @@ fn get_max(registers: &HashMap<String, isize>) -> Option<isize> {
         .max()
 }
 
-#[aoc(day8, part1)]
-fn problem1(input: &Vec<Instruction>) -> Result<isize, ParseError> {
+type Registers = HashMap<String, isize>;
+
+// Executes all instructions and calls `inspect` after each one. The
+// error carries the registers.
+fn run<F>(input: &[Instruction], budget: &mut Budget, mut inspect: F) -> Result<Registers, Exhausted<Registers>>
+    where F: FnMut(&Registers) {
+
     let mut registers = HashMap::new();
 
     for i in input {
+        budget.spend(|| registers.clone())?;
         i.execute(&mut registers);
+        inspect(&registers);
     }
 
+    Ok(registers)
+}
+
+const BUDGET: usize = 1_000_000;
+
+#[aoc(day8, part1)]
+fn problem1(input: &[Instruction]) -> Result<isize, ParseError> {
+    let registers = run(input, &mut Budget::new(BUDGET), |_| {})?;
+
     let max = get_max(&registers).ok_or(ParseError::new("Could not determine max value"))?;
     Ok(max)
 }
 
 #[aoc(day8, part2)]
-fn problem2(input: &Vec<Instruction>) -> Result<isize, ParseError> {
-    let mut registers = HashMap::new();
-    let mut max = isize::MIN;
+fn problem2(input: &[Instruction]) -> Result<isize, ParseError> {
+    let mut max = None;
 
-    for i in input {
-        i.execute(&mut registers);
-        let current_max = get_max(&registers).ok_or(ParseError::new("Could not determine max value"))?;
-        max = std::cmp::max(max, current_max);
-    }
+    run(input, &mut Budget::new(BUDGET), |registers| {
+        max = std::cmp::max(max, get_max(registers));
+    })?;
 
+    let max = max.ok_or(ParseError::new("Could not determine max value"))?;
     Ok(max)
 }
@@ -0,0 +1,570 @@
+use std::fmt::{Error, Display, Formatter, Write};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+use crate::render;
+use crate::utils::ParseError;
+use crate::utils::grid::Grid;
+
+// A square of 2x2, 3x3 or 4x4 pixels, bit y * size + x is the pixel
+// at (x, y).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Tile {
+    pub size: usize,
+    pub bits: u16,
+}
+
+impl Tile {
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.bits >> (y * self.size + x) & 1 == 1
+    }
+
+    fn from_fn<F: Fn(usize, usize) -> bool>(size: usize, f: F) -> Self {
+        let mut bits = 0;
+        for y in 0..size {
+            for x in 0..size {
+                if f(x, y) {
+                    bits |= 1 << (y * size + x);
+                }
+            }
+        }
+
+        Tile { size, bits }
+    }
+
+    // Clockwise by 90 degrees.
+    pub fn rotate(&self) -> Self {
+        Tile::from_fn(self.size, |x, y| self.get(y, self.size - 1 - x))
+    }
+
+    // Left to right.
+    pub fn flip(&self) -> Self {
+        Tile::from_fn(self.size, |x, y| self.get(self.size - 1 - x, y))
+    }
+
+    // All rotations, flipped and not.
+    pub fn transformations(&self) -> [Tile; 8] {
+        let mut result = [*self; 8];
+
+        let mut t = *self;
+        for i in 0..4 {
+            result[2 * i] = t;
+            result[2 * i + 1] = t.flip();
+            t = t.rotate();
+        }
+
+        result
+    }
+
+    // The same for all transformations of a tile.
+    pub fn canonical(&self) -> Self {
+        *self.transformations().iter().min().unwrap()
+    }
+}
+
+impl FromStr for Tile {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let rows = s.trim().split('/').collect::<Vec<_>>();
+        let size = rows.len();
+
+        if !(2..=4).contains(&size) || rows.iter().any(|r| r.chars().count() != size) {
+            return Err(ParseError::new(&format!("Invalid pattern '{}'", s.trim())));
+        }
+
+        let mut bits = 0;
+        for (y, r) in rows.iter().enumerate() {
+            for (x, c) in r.chars().enumerate() {
+                match c {
+                    '#' => bits |= 1 << (y * size + x),
+                    '.' => (),
+                    _ => return Err(ParseError::new(&format!("Invalid pixel '{}'", c))),
+                }
+            }
+        }
+
+        Ok(Tile { size, bits })
+    }
+}
+
+impl Display for Tile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        for y in 0..self.size {
+            if y > 0 {
+                f.write_char('/')?;
+            }
+
+            for x in 0..self.size {
+                f.write_char(if self.get(x, y) { '#' } else { '.' })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub const START: &str = ".#./..#/###";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub data: Grid<bool>,
+}
+
+impl Pattern {
+    pub fn init() -> Self {
+        Pattern::from(Tile::from_str(START).unwrap())
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.width()
+    }
+
+    fn tile(&self, x: usize, y: usize, size: usize) -> Tile {
+        Tile::from_fn(size, |tx, ty| *self.data.get(((x + tx) as isize, (y + ty) as isize)))
+    }
+
+    pub fn lit(&self) -> usize {
+        self.data.iter().filter(|(_, b)| **b).count()
+    }
+
+    pub fn to_image(&self, format: Format) -> Vec<u8> {
+        let pixel = |x: usize, y: usize| *self.data.get((x as isize, y as isize));
+
+        match format {
+            Format::Pbm => render::pbm(self.size(), self.size(), pixel),
+            Format::Png => render::png(self.size(), self.size(), pixel),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Pbm,
+    Png,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Pbm => "pbm",
+            Format::Png => "png",
+        }
+    }
+}
+
+impl From<Tile> for Pattern {
+    fn from(tile: Tile) -> Self {
+        let mut data = Grid::dense(tile.size, tile.size, false);
+        for y in 0..tile.size {
+            for x in 0..tile.size {
+                data.set((x as isize, y as isize), tile.get(x, y));
+            }
+        }
+
+        Pattern { data }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        for y in 0..self.size() {
+            for x in 0..self.size() {
+                f.write_char(if *self.data.get((x as isize, y as isize)) { '#' } else { '.' })?;
+            }
+
+            f.write_char('\n')?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Enhancement {
+    pub from: Tile,
+    pub to: Tile,
+    // 1-based line in the enhancement book, 0 if unknown.
+    pub line: usize,
+}
+
+impl FromStr for Enhancement {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        let rule = s
+            .split(" => ")
+            .map(|p| p.trim())
+            .collect::<Vec<_>>();
+
+        if rule.len() != 2 {
+            Err(ParseError::new("Could not parse rule"))?;
+        }
+
+        let from = Tile::from_str(rule[0])?;
+        let to = Tile::from_str(rule[1])?;
+
+        Ok(Enhancement { from, to, line: 0 })
+    }
+}
+
+// Parses one rule per line, without checking the sizes.
+pub fn parse(input: &str) -> Result<Vec<Enhancement>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, l)| Enhancement::from_str(l)
+            .map(|e| Enhancement { line: i + 1, ..e })
+            .map_err(|e| ParseError::new(&format!("line {}: {}", i + 1, e))))
+        .collect::<Result<Vec<_>, ParseError>>()
+}
+
+// Parses the rules and rejects malformed ones.
+pub fn load(input: &str) -> Result<Vec<Enhancement>, ParseError> {
+    let enhancements = parse(input)?;
+    reject_malformed(&enhancements)?;
+
+    Ok(enhancements)
+}
+
+// Fails on the first rule that does not map 2x2 to 3x3 or 3x3 to 4x4.
+pub fn reject_malformed(enhancements: &[Enhancement]) -> Result<(), ParseError> {
+    match validate(enhancements).iter().find(|p| matches!(p, Problem::Malformed { .. })) {
+        Some(p) => Err(ParseError::new(&p.to_string())),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    // Only 2x2 -> 3x3 and 3x3 -> 4x4 rules make sense.
+    Malformed { line: usize, from: usize, to: usize },
+    // No rule for any transformation of the pattern.
+    Missing(Tile),
+    // The input is a transformation of the input of an earlier rule.
+    Duplicate { line: usize, first: usize },
+    // Like a duplicate, but with a different output. The first rule wins.
+    Conflict { line: usize, first: usize },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Problem::Malformed { line, from, to } =>
+                write!(f, "line {}: rule turns {}x{} into {}x{}", line, from, from, to, to),
+            Problem::Missing(tile) => write!(f, "no rule for {}", tile),
+            Problem::Duplicate { line, first } => write!(f, "line {}: same rule as line {}", line, first),
+            Problem::Conflict { line, first } => write!(f, "line {}: conflicts with line {}", line, first),
+        }
+    }
+}
+
+// Checks the rules for malformed sizes, duplicates and conflicts in the
+// order of the book, followed by all patterns without a rule in their
+// canonical form.
+pub fn validate(enhancements: &[Enhancement]) -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut seen: HashMap<Tile, &Enhancement> = HashMap::new();
+
+    for e in enhancements {
+        if e.from.size == 4 || e.to.size != e.from.size + 1 {
+            problems.push(Problem::Malformed { line: e.line, from: e.from.size, to: e.to.size });
+            continue;
+        }
+
+        match seen.get(&e.from.canonical()) {
+            Some(first) if first.to == e.to => problems.push(Problem::Duplicate { line: e.line, first: first.line }),
+            Some(first) => problems.push(Problem::Conflict { line: e.line, first: first.line }),
+            None => {
+                seen.insert(e.from.canonical(), e);
+            },
+        }
+    }
+
+    for size in 2..=3 {
+        let mut missing = (0..1 << (size * size))
+            .map(|bits| Tile { size, bits }.canonical())
+            .filter(|t| !seen.contains_key(t))
+            .collect::<Vec<_>>();
+
+        missing.sort();
+        missing.dedup();
+        problems.extend(missing.into_iter().map(Problem::Missing));
+    }
+
+    problems
+}
+
+// The output for every 2x2 and 3x3 tile, indexed by its bits. Rules are
+// looked up by the canonical form of their input, the first rule wins.
+// Malformed rules are ignored.
+pub struct Book {
+    two: [Option<Tile>; 1 << 4],
+    three: [Option<Tile>; 1 << 9],
+}
+
+impl Book {
+    pub fn new(enhancements: &[Enhancement]) -> Self {
+        let mut rules = HashMap::new();
+        for e in enhancements.iter().filter(|e| e.to.size == e.from.size + 1) {
+            rules.entry(e.from.canonical()).or_insert(e.to);
+        }
+
+        let mut book = Book { two: [None; 1 << 4], three: [None; 1 << 9] };
+        for (bits, entry) in book.two.iter_mut().enumerate() {
+            *entry = rules.get(&Tile { size: 2, bits: bits as u16 }.canonical()).cloned();
+        }
+        for (bits, entry) in book.three.iter_mut().enumerate() {
+            *entry = rules.get(&Tile { size: 3, bits: bits as u16 }.canonical()).cloned();
+        }
+
+        book
+    }
+
+    pub fn get(&self, tile: Tile) -> Option<Tile> {
+        match tile.size {
+            2 => self.two[tile.bits as usize],
+            3 => self.three[tile.bits as usize],
+            _ => None,
+        }
+    }
+}
+
+impl Pattern {
+    pub fn enhance(&self, book: &Book) -> Result<Self, ParseError> {
+        let step = if self.size().is_multiple_of(2) {
+            2
+        } else {
+            3
+        };
+
+        let tiles = self.size() / step;
+        let mut data = Grid::dense(tiles * (step + 1), tiles * (step + 1), false);
+
+        for ty in 0..tiles {
+            for tx in 0..tiles {
+                let tile = self.tile(tx * step, ty * step, step);
+                let replacement = book.get(tile)
+                    .ok_or(ParseError::new(&format!("No rule for {}, canonical form {}", tile, tile.canonical())))?;
+
+                let x = tx * (step + 1);
+                let y = ty * (step + 1);
+                for py in 0..step + 1 {
+                    for px in 0..step + 1 {
+                        data.set(((x + px) as isize, (y + py) as isize), replacement.get(px, py));
+                    }
+                }
+            }
+        }
+
+        Ok(Pattern { data })
+    }
+}
+
+pub fn run(enhancements: &[Enhancement], iterations: usize) -> Result<Pattern, ParseError> {
+    let book = Book::new(enhancements);
+    let mut pattern = Pattern::init();
+
+    for _ in 0..iterations {
+        pattern = pattern.enhance(&book)?;
+    }
+
+    Ok(pattern)
+}
+
+// Counts lit pixels without building the image. After three iterations
+// a 3x3 tile has become a 9x9 pattern whose nine 3x3 tiles evolve
+// independently of each other, so the counts of all 3x3 tiles are built
+// up three iterations at a time from the counts of their nine tiles.
+// Counts are capped at u128::MAX, which the puzzle input exceeds after
+// about 200 iterations. Once the counts stop changing, the remaining
+// levels are skipped.
+pub fn count(enhancements: &[Enhancement], iterations: usize) -> Result<u128, ParseError> {
+    let book = Book::new(enhancements);
+    let start = Tile::from_str(START)?;
+    let tiles = (0..1 << 9).map(|bits| Tile { size: 3, bits }).collect::<Vec<_>>();
+
+    let expand = |tile: Tile, iterations: usize| -> Result<Pattern, Rc<str>> {
+        let mut pattern = Pattern::from(tile);
+        for _ in 0..iterations {
+            pattern = pattern.enhance(&book).map_err(|e| Rc::from(e.what))?;
+        }
+
+        Ok(pattern)
+    };
+
+    // at least one iteration before the first level, unless there are none
+    let first = if iterations == 0 { 0 } else { (iterations - 1) % 3 + 1 };
+    let mut counts = tiles.iter()
+        .map(|t| expand(*t, first).map(|p| p.lit() as u128))
+        .collect::<Vec<_>>();
+
+    let levels = (iterations - first) / 3;
+    if levels > 0 {
+        let children = tiles.iter()
+            .map(|t| expand(*t, 3).map(|p| {
+                (0..9).step_by(3)
+                    .flat_map(|y| (0..9).step_by(3).map(move |x| (x, y)))
+                    .map(|(x, y)| p.tile(x, y, 3).bits as usize)
+                    .collect::<Vec<_>>()
+            }))
+            .collect::<Vec<_>>();
+
+        for _ in 0..levels {
+            let next = children.iter()
+                .map(|c| c.as_ref().map_err(Rc::clone).and_then(|c| c.iter()
+                    .try_fold(0u128, |sum, child| {
+                        let lit = counts[*child].as_ref().map_err(Rc::clone)?;
+                        sum.checked_add(*lit).ok_or_else(|| Rc::from("Too many lit pixels to count"))
+                    })))
+                .collect::<Vec<_>>();
+
+            if next == counts {
+                break;
+            }
+            counts = next;
+        }
+    }
+
+    counts.swap_remove(start.bits as usize).map_err(|e| ParseError::new(&e))
+}
+
+// Writes the image after every iteration, including the start, to
+// numbered files in `dir`.
+pub fn dump(enhancements: &[Enhancement], iterations: usize, dir: &Path, format: Format) -> Result<Vec<PathBuf>, ParseError> {
+    let book = Book::new(enhancements);
+    let digits = iterations.to_string().len();
+    let mut pattern = Pattern::init();
+    let mut files = vec![];
+
+    fs::create_dir_all(dir).map_err(|e| ParseError::new(&e.to_string()))?;
+
+    for i in 0..=iterations {
+        if i > 0 {
+            pattern = pattern.enhance(&book)?;
+        }
+
+        let file = dir.join(format!("iteration-{:0width$}.{}", i, format.extension(), width = digits));
+        fs::write(&file, pattern.to_image(format)).map_err(|e| ParseError::new(&format!("{}: {}", file.display(), e)))?;
+        files.push(file);
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn example() {
+        let enhancements = load(include_str!("../day21/example")).unwrap();
+        let pattern = run(&enhancements, 2).unwrap();
+
+        assert_eq!("##.##.\n#..#..\n......\n##.##.\n#..#..\n......\n", pattern.to_string());
+        assert_eq!(12, pattern.lit());
+
+        // most tiles have no rule in the example, which only matters
+        // once they are reached
+        assert_eq!(12, count(&enhancements, 2).unwrap());
+        assert_eq!(run(&enhancements, 3).is_ok(), count(&enhancements, 3).is_ok());
+        assert!(count(&enhancements, 4).is_err());
+    }
+
+    #[test]
+    pub fn counting_matches_the_image() {
+        let enhancements = load(include_str!("../../input/2017/day21.txt")).unwrap();
+
+        for iterations in 0..=10 {
+            let lit = run(&enhancements, iterations).unwrap().lit();
+            assert_eq!(lit as u128, count(&enhancements, iterations).unwrap());
+        }
+    }
+
+    #[test]
+    pub fn counting_many_iterations() {
+        let enhancements = load(include_str!("../../input/2017/day21.txt")).unwrap();
+
+        assert!(count(&enhancements, 100).unwrap() > u64::MAX as u128);
+        assert!(count(&enhancements, 1000).is_err());
+
+        // far beyond the cap the counts are stable and this returns quickly
+        let error = count(&enhancements, 30_000).unwrap_err();
+        assert_eq!("Too many lit pixels to count", error.to_string());
+        assert!(count(&enhancements, usize::MAX).is_err());
+    }
+
+    #[test]
+    pub fn export() {
+        let pattern = Pattern::init();
+
+        assert_eq!(b"P4\n3 3\n\x40\x20\xe0".to_vec(), pattern.to_image(Format::Pbm));
+        assert_eq!(b"\x89PNG", &pattern.to_image(Format::Png)[..4]);
+    }
+
+    #[test]
+    pub fn dump_iterations() {
+        let enhancements = load(include_str!("../../input/2017/day21.txt")).unwrap();
+        let dir = std::env::temp_dir().join(format!("day21-dump-{}", std::process::id()));
+
+        let files = dump(&enhancements, 10, &dir, Format::Pbm).unwrap();
+        let last = fs::read(&files[10]).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(11, files.len());
+        assert!(files[3].ends_with("iteration-03.pbm"));
+        assert!(last.starts_with(b"P4\n108 108\n"));
+    }
+
+    #[test]
+    pub fn transformations() {
+        let tile = Tile::from_str(".#./..#/###").unwrap();
+        let all = tile.transformations().iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        assert!(all.contains(&"#../#.#/##.".to_string()));
+        assert!(all.contains(&".#./#../###".to_string()));
+        assert!(all.contains(&"###/..#/.#.".to_string()));
+        assert!(all.iter().all(|t| Tile::from_str(t).unwrap().canonical() == tile.canonical()));
+        assert_eq!(tile, tile.rotate().rotate().rotate().rotate());
+    }
+
+    #[test]
+    pub fn invalid_rules() {
+        assert!(load("../.# => ##./#../...").is_ok());
+        assert!(Enhancement::from_str("../.## => ##./#../...").is_err());
+        assert!(Enhancement::from_str("../.x => ##./#../...").is_err());
+
+        let error = load("../.# => ##./#../...\n../.# => ##../#.../..../....").unwrap_err();
+        assert_eq!("line 2: rule turns 2x2 into 4x4", error.to_string());
+
+        let error = load("../.# => ##./#../...\n../.# => ##./#../..x").unwrap_err();
+        assert_eq!("line 2: Invalid pixel 'x'", error.to_string());
+    }
+
+    #[test]
+    pub fn complete_book() {
+        let enhancements = load(include_str!("../../input/2017/day21.txt")).unwrap();
+
+        assert_eq!(Vec::<Problem>::new(), validate(&enhancements));
+    }
+
+    #[test]
+    pub fn validation() {
+        let book = "\
+../.# => ##./#../...
+#./.. => ##./#../...
+.#/.. => ##./##./...
+##/## => ####/####/####/####
+.#./..#/### => #..#/..../..../#..#";
+        let problems = validate(&parse(book).unwrap());
+
+        assert_eq!(Problem::Duplicate { line: 2, first: 1 }, problems[0]);
+        assert_eq!(Problem::Conflict { line: 3, first: 1 }, problems[1]);
+        assert_eq!(Problem::Malformed { line: 4, from: 2, to: 4 }, problems[2]);
+        assert_eq!(Problem::Missing(Tile::from_str("../..").unwrap()), problems[3]);
+        assert_eq!(3 + 5 + 101, problems.len());
+        assert_eq!("line 3: conflicts with line 1", problems[1].to_string());
+    }
+}
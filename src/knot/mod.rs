@@ -1,44 +1,238 @@
-fn init(size: usize) -> Vec<usize> {
-    (vec![0; size]).into_iter().enumerate().map(|(i, _)| i).collect::<Vec<_>>()
+use std::hash::BuildHasher;
+
+const SUFFIX: [u8; 5] = [17, 31, 73, 47, 23];
+
+const SIZE: usize = 256;
+const ROUNDS: usize = 64;
+
+// The circle of marks. Only the first `size` entries of `marks` are used
+// and all indices wrap around at `size`.
+#[derive(Debug, Clone)]
+pub struct Knot {
+    marks: [u8; SIZE],
+    size: usize,
+    pos: usize,
+    skip: usize,
+}
+
+impl Knot {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0 && size <= SIZE, "Circle size must be between 1 and {}", SIZE);
+
+        let mut marks = [0; SIZE];
+        for (i, m) in marks.iter_mut().enumerate() {
+            *m = i as u8;
+        }
+
+        Knot { marks, size, pos: 0, skip: 0 }
+    }
+
+    // Reverses `length` marks starting at the current position in place.
+    pub fn tie(&mut self, length: usize) {
+        let last = self.pos + length + self.size - 1;
+        for i in 0..length / 2 {
+            self.marks.swap((self.pos + i) % self.size, (last - i) % self.size);
+        }
+
+        self.pos = (self.pos + length + self.skip) % self.size;
+        self.skip += 1;
+    }
+
+    pub fn rounds(&mut self, lengths: &[u8], rounds: usize) {
+        for _ in 0..rounds {
+            for l in lengths {
+                self.tie(*l as usize);
+            }
+        }
+    }
+
+    pub fn marks(&self) -> &[u8] {
+        &self.marks[..self.size]
+    }
+}
+
+fn densify(sparse: &[u8]) -> [u8; 16] {
+    let mut dense = [0; 16];
+    for (i, digit) in dense.iter_mut().enumerate() {
+        *digit = sparse.iter().skip(i * 16).take(16).fold(0, |acc, v| acc ^ v);
+    }
+
+    dense
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnotHash {
+    pub sparse: [u8; SIZE],
+    pub dense: [u8; 16],
+}
+
+impl KnotHash {
+    pub fn new(input: &[u8]) -> Self {
+        let mut hasher = Hasher::new();
+        hasher.update(input);
+        hasher.finalize()
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.dense.iter().map(|v| format!("{:02x}", v)).collect::<String>()
+    }
+
+    // The digest as 128 binary digits.
+    pub fn to_binary(&self) -> String {
+        format!("{:0128b}", self.to_bits())
+    }
+
+    pub fn to_base64(&self) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        let mut result = String::new();
+        for chunk in self.dense.chunks(3) {
+            let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+            let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    result.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    result.push('=');
+                }
+            }
+        }
+
+        result
+    }
+
+    // The digest as a number, the first byte is the most significant one.
+    pub fn to_bits(&self) -> u128 {
+        u128::from_be_bytes(self.dense)
+    }
 }
 
-fn tick(v: &Vec<usize>, pos: usize, length: usize) -> Vec<usize> {
-    let v_len = v.len();
-    let iter_rot = v.iter();
-    let iter_copy = v.iter();
+// Collects the input piece by piece. The lengths are applied in 64
+// rounds, so nothing can be hashed before the input is complete.
+#[derive(Debug, Clone, Default)]
+pub struct Hasher {
+    lengths: Vec<u8>,
+}
+
+impl Hasher {
+    pub fn new() -> Self {
+        Hasher::default()
+    }
+
+    pub fn update(&mut self, input: &[u8]) -> &mut Self {
+        self.lengths.extend_from_slice(input);
+        self
+    }
+
+    pub fn finalize(&self) -> KnotHash {
+        let mut lengths = self.lengths.clone();
+        lengths.extend_from_slice(&SUFFIX);
 
-    let rot = iter_rot.cycle().skip(pos).take(length).collect::<Vec<_>>().into_iter().rev();
-    let copy = iter_copy.cycle().skip(pos + length).take(v_len - length);
+        let mut knot = Knot::new(SIZE);
+        knot.rounds(&lengths, ROUNDS);
 
-    rot.chain(copy).cycle().skip(v_len - pos).take(v_len).cloned().collect::<Vec<_>>()
+        let dense = densify(&knot.marks);
+        KnotHash { sparse: knot.marks, dense }
+    }
 }
 
-fn hashify(raw: &Vec<usize>) -> String {
-    let mut dense = vec![];
-    for i in 0..16 {
-        let digit = raw.iter().skip(i * 16).take(16).fold(0, |acc, v| acc ^ v);
-        dense.push(digit);
+// Makes the knot hash usable as the hash function of a `HashMap`. The
+// builder hands out copies of itself, so anything written to it before
+// acts as a salt for all hashes.
+#[derive(Debug, Clone, Default)]
+pub struct KnotHasher {
+    hasher: Hasher,
+}
+
+impl KnotHasher {
+    pub fn new() -> Self {
+        KnotHasher::default()
     }
+}
 
-    dense.iter().map(|v| format!("{:02x}", v)).collect::<String>()
+impl std::hash::Hasher for KnotHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher.update(bytes);
+    }
+
+    // The first eight bytes of the digest.
+    fn finish(&self) -> u64 {
+        (self.hasher.finalize().to_bits() >> 64) as u64
+    }
+}
+
+impl BuildHasher for KnotHasher {
+    type Hasher = KnotHasher;
+
+    fn build_hasher(&self) -> KnotHasher {
+        self.clone()
+    }
 }
 
 pub fn hash(input: &str) -> String {
-    let mut lengths = input.trim().chars().map(|c| c as usize).collect::<Vec<_>>();
-    let mut append = vec![17, 31, 73, 47, 23];
-    lengths.append(&mut append);
-
-    let mut vec = init(256);
-    let mut pos = 0;
-    let mut skip = 0;
-
-    for _ in 0..64 {
-        for l in &lengths {
-            vec = tick(&vec, pos, *l);
-            pos = (pos + *l + skip) % vec.len();
-            skip += 1;
+    KnotHash::new(input.trim().as_bytes()).to_hex()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn tie_example() {
+        let mut knot = Knot::new(5);
+        for l in &[3, 4, 1, 5] {
+            knot.tie(*l);
         }
+
+        assert_eq!(&[3, 4, 2, 1, 0], knot.marks());
+    }
+
+    #[test]
+    pub fn hex() {
+        assert_eq!("a2582a3a0e66e6e86e3812dcb672a272", hash(""));
+        assert_eq!("33efeb34ea91902bb2f59c9920caa6cd", hash("AoC 2017"));
+        assert_eq!("3efbe78a8d82f29979031a4aa0b16a9d", hash("1,2,3"));
+        assert_eq!("63960835bcdc130f0b66d7ff4f6a5a8e", hash("1,2,4"));
+    }
+
+    #[test]
+    pub fn bits() {
+        let h = KnotHash::new(b"1,2,3");
+
+        assert_eq!(0x3efbe78a8d82f29979031a4aa0b16a9d, h.to_bits());
+        assert_eq!(256, h.sparse.len());
     }
 
-    hashify(&vec)
+    #[test]
+    pub fn formats() {
+        let h = KnotHash::new(b"");
+
+        assert_eq!("olgqOg5m5uhuOBLctnKicg==", h.to_base64());
+        assert_eq!(128, h.to_binary().len());
+        assert!(h.to_binary().starts_with("10100010"));
+    }
+
+    #[test]
+    pub fn hash_map() {
+        use std::collections::HashMap;
+        use std::hash::Hasher as _;
+
+        let mut hasher = KnotHasher::new();
+        hasher.write(b"AoC 2017");
+        assert_eq!(0x33efeb34ea91902b, hasher.finish());
+
+        let mut map = HashMap::with_hasher(KnotHasher::new());
+        map.insert("flqrgnkx", 8108);
+        map.insert("AoC 2017", 2017);
+        assert_eq!(Some(&8108), map.get("flqrgnkx"));
+    }
+
+    #[test]
+    pub fn update_in_pieces() {
+        let mut hasher = Hasher::new();
+        hasher.update(b"AoC").update(b" ").update(b"2017");
+
+        assert_eq!(KnotHash::new(b"AoC 2017"), hasher.finalize());
+    }
 }
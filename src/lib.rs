@@ -32,7 +32,14 @@ mod day22;
 mod day23;
 mod day24;
 mod day25;
-mod utils;
-mod knot;
+pub mod utils;
+pub mod knot;
+pub mod vm;
+pub mod turing;
+pub mod regions;
+pub mod carrier;
+pub mod render;
+pub mod fractal;
+pub mod spiral;
 
 aoc_lib!{ year = 2017 }
\ No newline at end of file
@@ -0,0 +1,194 @@
+// Connected-component labelling of grids with a two-pass scanline over
+// a union-find of provisional labels. Cells are connected to their four
+// direct neighbours.
+
+use crate::render;
+
+#[derive(Debug, Clone, Default)]
+struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    fn add(&mut self) -> usize {
+        self.parents.push(self.parents.len());
+        self.parents.len() - 1
+    }
+
+    fn find(&mut self, mut a: usize) -> usize {
+        while self.parents[a] != a {
+            self.parents[a] = self.parents[self.parents[a]];
+            a = self.parents[a];
+        }
+
+        a
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a != b {
+            self.parents[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub size: usize,
+    // Top left and bottom right corner, both inclusive.
+    pub min: (usize, usize),
+    pub max: (usize, usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Labelling {
+    pub width: usize,
+    pub height: usize,
+    // Row by row, the index into `regions` of every set cell.
+    pub labels: Vec<Option<usize>>,
+    // Ordered by the first cell of each region in reading order.
+    pub regions: Vec<Region>,
+}
+
+// Labels all cells of a `width` x `height` grid for which `is_set` is true.
+pub fn label<F: Fn(usize, usize) -> bool>(width: usize, height: usize, is_set: F) -> Labelling {
+    let mut sets = UnionFind::default();
+    let mut provisional: Vec<Option<usize>> = vec![None; width * height];
+
+    for y in 0..height {
+        for x in 0..width {
+            if !is_set(x, y) {
+                continue;
+            }
+
+            let left = if x > 0 { provisional[y * width + x - 1] } else { None };
+            let up = if y > 0 { provisional[(y - 1) * width + x] } else { None };
+
+            provisional[y * width + x] = Some(match (left, up) {
+                (Some(l), Some(u)) => {
+                    sets.union(l, u);
+                    l
+                },
+                (Some(l), None) => l,
+                (None, Some(u)) => u,
+                (None, None) => sets.add(),
+            });
+        }
+    }
+
+    // provisional roots to final labels, in order of appearance
+    let mut names: Vec<Option<usize>> = vec![None; sets.parents.len()];
+    let mut regions: Vec<Region> = vec![];
+    let mut labels = vec![None; width * height];
+
+    for (i, p) in provisional.iter().enumerate() {
+        let root = match p {
+            Some(p) => sets.find(*p),
+            None => continue,
+        };
+        let (x, y) = (i % width, i / width);
+
+        let name = *names[root].get_or_insert_with(|| {
+            regions.push(Region { size: 0, min: (x, y), max: (x, y) });
+            regions.len() - 1
+        });
+
+        let region = &mut regions[name];
+        region.size += 1;
+        region.min = (region.min.0.min(x), region.min.1.min(y));
+        region.max = (region.max.0.max(x), region.max.1.max(y));
+        labels[i] = Some(name);
+    }
+
+    Labelling { width, height, labels, regions }
+}
+
+impl Labelling {
+    pub fn get(&self, x: usize, y: usize) -> Option<usize> {
+        self.labels[y * self.width + x]
+    }
+
+    // One character per cell, regions cycle through digits and letters,
+    // unset cells are '.'. A grid without columns gives an empty string.
+    pub fn to_text(&self) -> String {
+        const SYMBOLS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+        self.labels.chunks(self.width.max(1))
+            .map(|row| row.iter()
+                .map(|l| l.map(|l| SYMBOLS[l % SYMBOLS.len()] as char).unwrap_or('.'))
+                .collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // A binary PPM image with `scale` x `scale` pixels per cell. Each
+    // region gets its own colour, unset cells are black. A grid without
+    // columns gives only the header.
+    pub fn to_ppm(&self, scale: usize) -> Vec<u8> {
+        let mut result = format!("P6\n{} {}\n255\n", self.width * scale, self.height * scale).into_bytes();
+
+        for row in self.labels.chunks(self.width.max(1)) {
+            let pixels = row.iter()
+                .flat_map(|l| {
+                    let colour = l.map(|l| render::colour(l as u32 + 1)).unwrap_or([0, 0, 0]);
+                    std::iter::repeat_n(colour, scale).flatten()
+                })
+                .collect::<Vec<_>>();
+
+            for _ in 0..scale {
+                result.extend_from_slice(&pixels);
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn grid(text: &str) -> Labelling {
+        let rows = text.lines().map(|l| l.as_bytes()).collect::<Vec<_>>();
+        label(rows[0].len(), rows.len(), |x, y| rows[y][x] == b'#')
+    }
+
+    #[test]
+    pub fn merges_regions_meeting_later() {
+        let labelling = grid("#.#\n#.#\n###\n...\n.#.");
+
+        assert_eq!(2, labelling.regions.len());
+        assert_eq!(Region { size: 7, min: (0, 0), max: (2, 2) }, labelling.regions[0]);
+        assert_eq!(Region { size: 1, min: (1, 4), max: (1, 4) }, labelling.regions[1]);
+        assert_eq!("0.0\n0.0\n000\n...\n.1.", labelling.to_text());
+    }
+
+    #[test]
+    pub fn diagonals_are_not_connected() {
+        let labelling = grid("#.\n.#");
+
+        assert_eq!(Some(0), labelling.get(0, 0));
+        assert_eq!(Some(1), labelling.get(1, 1));
+        assert_eq!(None, labelling.get(1, 0));
+    }
+
+    #[test]
+    pub fn ppm() {
+        let ppm = grid("#.").to_ppm(2);
+        let header = b"P6\n4 2\n255\n";
+
+        assert_eq!(header.len() + 4 * 2 * 3, ppm.len());
+        assert_eq!(&ppm[header.len()..header.len() + 3], &render::colour(1));
+        assert_eq!(&ppm[header.len() + 6..header.len() + 9], &[0, 0, 0]);
+    }
+
+    #[test]
+    pub fn zero_width() {
+        let labelling = label(0, 3, |_, _| true);
+
+        assert!(labelling.regions.is_empty());
+        assert_eq!("", labelling.to_text());
+        assert_eq!(b"P6\n0 6\n255\n".to_vec(), labelling.to_ppm(2));
+    }
+}
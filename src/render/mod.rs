@@ -0,0 +1,441 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::utils::grid::{Grid, Point};
+
+// Anything that can be drawn cell by cell.
+pub trait Scene {
+    fn cell(&self, p: Point) -> char;
+
+    // Top left and bottom right corner of the interesting part.
+    fn bounds(&self) -> Option<(Point, Point)>;
+}
+
+impl Scene for Grid<char> {
+    fn cell(&self, p: Point) -> char {
+        *self.get(p)
+    }
+
+    fn bounds(&self) -> Option<(Point, Point)> {
+        Grid::bounds(self)
+    }
+}
+
+// Draws `marker` on top of a scene, e.g. for the position of a walker.
+pub struct Marked<'a> {
+    pub scene: &'a dyn Scene,
+    pub at: Point,
+    pub marker: char,
+}
+
+impl<'a> Scene for Marked<'a> {
+    fn cell(&self, p: Point) -> char {
+        if p == self.at {
+            self.marker
+        } else {
+            self.scene.cell(p)
+        }
+    }
+
+    fn bounds(&self) -> Option<(Point, Point)> {
+        self.scene.bounds()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub min: Point,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Viewport {
+    pub fn new(min: Point, width: usize, height: usize) -> Self {
+        Viewport { min, width, height }
+    }
+
+    pub fn around(center: Point, width: usize, height: usize) -> Self {
+        Viewport::new((center.0 - width as isize / 2, center.1 - height as isize / 2), width, height)
+    }
+
+    // Just large enough for the current bounds of the scene.
+    pub fn fit(scene: &dyn Scene) -> Self {
+        match scene.bounds() {
+            Some((min, max)) => Viewport::new(min, (max.0 - min.0 + 1) as usize, (max.1 - min.1 + 1) as usize),
+            None => Viewport::new((0, 0), 1, 1),
+        }
+    }
+
+    pub fn capture(&self, scene: &dyn Scene) -> Grid<char> {
+        let mut frame = Grid::dense(self.width, self.height, ' ');
+
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                frame.set((x, y), scene.cell((self.min.0 + x, self.min.1 + y)));
+            }
+        }
+
+        frame
+    }
+}
+
+// Frames of a simulation, one every `interval` steps.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub viewport: Viewport,
+    pub interval: usize,
+    // The step and the content of the viewport.
+    pub frames: Vec<(usize, Grid<char>)>,
+}
+
+impl Animation {
+    pub fn new(viewport: Viewport, interval: usize) -> Self {
+        Animation { viewport, interval: interval.max(1), frames: vec![] }
+    }
+
+    // Captures the scene if `step` is due.
+    pub fn record(&mut self, step: usize, scene: &dyn Scene) {
+        if step.is_multiple_of(self.interval) {
+            self.frames.push((step, self.viewport.capture(scene)));
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        self.frames.iter()
+            .map(|(step, frame)| format!("step {}\n{}\n", step, frame))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // An endlessly looping GIF with `scale` x `scale` pixels per cell
+    // and `delay` hundredths of a second between frames. Cells are
+    // coloured through `palette`, other characters get made up colours.
+    // Fails if a scaled frame does not fit the 16 bit size of a GIF.
+    pub fn to_gif(&self, palette: &[(char, [u8; 3])], scale: usize, delay: u16) -> io::Result<Vec<u8>> {
+        let size = |cells: usize| cells.checked_mul(scale)
+            .and_then(|s| u16::try_from(s).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Frames of {}x{} cells scaled by {} are too large for a GIF", self.viewport.width, self.viewport.height, scale)));
+        let width = size(self.viewport.width)?;
+        let height = size(self.viewport.height)?;
+
+        let mut colours = palette.to_vec();
+        for (_, frame) in &self.frames {
+            for (_, c) in frame.iter() {
+                if colours.len() < 256 && colours.iter().all(|(k, _)| k != c) {
+                    colours.push((*c, colour(*c as u32)));
+                }
+            }
+        }
+
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend_from_slice(&width.to_le_bytes());
+        gif.extend_from_slice(&height.to_le_bytes());
+        // global colour table with 256 entries
+        gif.extend_from_slice(&[0xf7, 0, 0]);
+        for i in 0..256 {
+            gif.extend_from_slice(&colours.get(i).map(|(_, rgb)| *rgb).unwrap_or([0, 0, 0]));
+        }
+
+        // loop forever
+        gif.extend_from_slice(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        for (_, frame) in &self.frames {
+            gif.extend_from_slice(&[0x21, 0xf9, 0x04, 0x04]);
+            gif.extend_from_slice(&delay.to_le_bytes());
+            gif.extend_from_slice(&[0, 0]);
+
+            gif.push(0x2c);
+            gif.extend_from_slice(&[0, 0, 0, 0]);
+            gif.extend_from_slice(&width.to_le_bytes());
+            gif.extend_from_slice(&height.to_le_bytes());
+            gif.push(0);
+
+            let pixels = (0..height as usize)
+                .flat_map(|y| (0..width as usize).map(move |x| (x / scale, y / scale)))
+                .map(|(x, y)| {
+                    let c = frame.get((x as isize, y as isize));
+                    colours.iter().position(|(k, _)| k == c).unwrap_or(0) as u8
+                })
+                .collect::<Vec<_>>();
+
+            gif.push(8);
+            for block in lzw(&pixels).chunks(255) {
+                gif.push(block.len() as u8);
+                gif.extend_from_slice(block);
+            }
+            gif.push(0);
+        }
+
+        gif.push(0x3b);
+        Ok(gif)
+    }
+
+    pub fn write_text<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn write_gif<P: AsRef<Path>>(&self, path: P, palette: &[(char, [u8; 3])], scale: usize, delay: u16) -> io::Result<()> {
+        fs::write(path, self.to_gif(palette, scale, delay)?)
+    }
+}
+
+// A made up colour for `key`, consecutive keys are spread over the
+// colour space and every colour is kept bright.
+pub fn colour(key: u32) -> [u8; 3] {
+    let h = key.wrapping_mul(2_654_435_761);
+    [(h >> 24) as u8 | 0x40, (h >> 16) as u8 | 0x40, (h >> 8) as u8 | 0x40]
+}
+
+const CLEAR: u16 = 256;
+const END: u16 = 257;
+// Codes between two clear codes, few enough that the decoder's table
+// never needs codes wider than 9 bits.
+const RUN: usize = 250;
+
+// LZW "compression" with 8 bit pixels that never builds a dictionary,
+// every pixel is its own 9 bit code.
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    let mut emit = |code: u16| {
+        buffer |= (code as u32) << bits;
+        bits += 9;
+        while bits >= 8 {
+            out.push(buffer as u8);
+            buffer >>= 8;
+            bits -= 8;
+        }
+    };
+
+    for run in pixels.chunks(RUN) {
+        emit(CLEAR);
+        for p in run {
+            emit(*p as u16);
+        }
+    }
+    emit(END);
+
+    if bits > 0 {
+        out.push(buffer as u8);
+    }
+
+    out
+}
+
+// A binary PBM image, set pixels are black.
+pub fn pbm<F: Fn(usize, usize) -> bool>(width: usize, height: usize, pixel: F) -> Vec<u8> {
+    let mut result = format!("P4\n{} {}\n", width, height).into_bytes();
+    for y in 0..height {
+        result.extend(pack(width, |x| pixel(x, y)));
+    }
+
+    result
+}
+
+// A black and white PNG, set pixels are black.
+pub fn png<F: Fn(usize, usize) -> bool>(width: usize, height: usize, pixel: F) -> Vec<u8> {
+    let mut raw = vec![];
+    for y in 0..height {
+        // filter type none, 0 is black in a grayscale image
+        raw.push(0);
+        raw.extend(pack(width, |x| !pixel(x, y)));
+    }
+
+    let mut header = vec![];
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // bit depth 1, grayscale, default compression, filter and no interlacing
+    header.extend_from_slice(&[1, 0, 0, 0, 0]);
+
+    let mut result = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut result, b"IHDR", &header);
+    chunk(&mut result, b"IDAT", &zlib(&raw));
+    chunk(&mut result, b"IEND", &[]);
+
+    result
+}
+
+// One bit per pixel, most significant bit first, rows padded to bytes.
+fn pack<F: Fn(usize) -> bool>(width: usize, pixel: F) -> Vec<u8> {
+    let mut row = vec![0u8; width.div_ceil(8)];
+    for x in (0..width).filter(|x| pixel(*x)) {
+        row[x / 8] |= 0x80 >> (x % 8);
+    }
+
+    row
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(kind.iter().chain(data));
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32<'a, I: Iterator<Item = &'a u8>>(data: I) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+// A zlib stream of uncompressed deflate blocks.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut result = vec![0x78, 0x01];
+
+    let blocks = data.chunks(0xffff).collect::<Vec<_>>();
+    for (i, block) in blocks.iter().enumerate() {
+        result.push((i + 1 == blocks.len()) as u8);
+        result.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        result.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        result.extend_from_slice(block);
+    }
+    if blocks.is_empty() {
+        result.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    result.extend_from_slice(&((b << 16) | a).to_be_bytes());
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Reads the 9 bit codes back, ignoring clear codes.
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let mut pixels = vec![];
+        let mut buffer = 0u32;
+        let mut bits = 0;
+
+        for byte in data {
+            buffer |= (*byte as u32) << bits;
+            bits += 8;
+
+            while bits >= 9 {
+                let code = (buffer & 0x1ff) as u16;
+                buffer >>= 9;
+                bits -= 9;
+
+                match code {
+                    CLEAR => continue,
+                    END => return pixels,
+                    _ => pixels.push(code as u8),
+                }
+            }
+        }
+
+        pixels
+    }
+
+    fn scene() -> Grid<char> {
+        Grid::parse("#.\n.#", '.', Ok).unwrap()
+    }
+
+    #[test]
+    pub fn lzw_round_trip() {
+        let pixels = (0..1000).map(|i| (i * 7 % 256) as u8).collect::<Vec<_>>();
+
+        assert_eq!(pixels, unlzw(&lzw(&pixels)));
+    }
+
+    #[test]
+    pub fn viewport_and_marker() {
+        let grid = scene();
+        let marked = Marked { scene: &grid, at: (1, 0), marker: '@' };
+
+        assert_eq!(Viewport::new((0, 0), 2, 2), Viewport::fit(&grid));
+        assert_eq!("...\n.#@\n..#", Viewport::new((-1, -1), 3, 3).capture(&marked).to_string());
+        assert_eq!(Viewport::new((-1, -1), 3, 3), Viewport::around((0, 0), 3, 3));
+    }
+
+    #[test]
+    pub fn frames_every_interval() {
+        let grid = scene();
+        let mut animation = Animation::new(Viewport::fit(&grid), 2);
+
+        for step in 0..5 {
+            animation.record(step, &grid);
+        }
+
+        assert_eq!(vec![0, 2, 4], animation.frames.iter().map(|(s, _)| *s).collect::<Vec<_>>());
+        assert!(animation.to_text().starts_with("step 0\n#.\n.#\n\nstep 2\n"));
+    }
+
+    #[test]
+    pub fn pbm_rows_are_padded() {
+        let image = pbm(10, 2, |x, y| x == y || x == 9);
+
+        assert_eq!(b"P4\n10 2\n\x80\x40\x40\x40".to_vec(), image);
+    }
+
+    #[test]
+    pub fn png_chunks() {
+        let image = png(10, 2, |x, y| x == y || x == 9);
+
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &image[..8]);
+        assert_eq!(b"\x00\x00\x00\x0dIHDR\x00\x00\x00\x0a\x00\x00\x00\x02\x01\x00", &image[8..26]);
+        assert_eq!(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82], &image[image.len() - 12..]);
+
+        // one stored block with the filter byte and inverted pixels per row
+        let idat = &image[33..image.len() - 12];
+        assert_eq!(b"IDAT", &idat[4..8]);
+        assert_eq!(&[0x78, 0x01, 1, 6, 0, 0xf9, 0xff], &idat[8..15]);
+        assert_eq!(&[0, 0x7f, 0x80, 0, 0xbf, 0x80], &idat[15..21]);
+    }
+
+    #[test]
+    pub fn checksums() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789".iter()));
+        assert_eq!(&[0x11, 0xe6, 0x03, 0x98], &zlib(b"Wikipedia")[16..]);
+    }
+
+    #[test]
+    pub fn gif() {
+        let grid = scene();
+        let mut animation = Animation::new(Viewport::fit(&grid), 1);
+        animation.record(0, &grid);
+
+        let gif = animation.to_gif(&[('.', [0, 0, 0]), ('#', [255, 0, 0])], 2, 10).unwrap();
+
+        assert_eq!(b"GIF89a\x04\x00\x04\x00", &gif[..10]);
+        assert_eq!(&[255, 0, 0], &gif[16..19]);
+        assert_eq!(Some(&0x3b), gif.last());
+
+        // header, palette, loop extension, control extension, descriptor
+        let data = &gif[13 + 768 + 19 + 8 + 10..];
+        assert_eq!(8, data[0]);
+        let len = data[1] as usize;
+        assert_eq!(vec![1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1], unlzw(&data[2..2 + len]));
+    }
+
+    #[test]
+    pub fn gif_too_large() {
+        let grid = scene();
+        let mut widest = Animation::new(Viewport::new((0, 0), 65_535, 1), 1);
+        widest.record(0, &grid);
+        let mut wider = Animation::new(Viewport::new((0, 0), 65_536, 1), 1);
+        wider.record(0, &grid);
+
+        assert!(widest.to_gif(&[], 1, 10).is_ok());
+        assert!(widest.to_gif(&[], 2, 10).is_err());
+        assert!(wider.to_gif(&[], 1, 10).is_err());
+        assert!(widest.to_gif(&[], usize::MAX, 10).is_err());
+    }
+}
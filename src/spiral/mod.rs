@@ -0,0 +1,394 @@
+use crate::utils::big::BigUint;
+use crate::utils::grid::{Connectivity, Grid, Point};
+
+// Square spirals around the origin. Index 0 is the origin and every
+// further index is one step away from the previous one. Coordinates
+// have y growing upwards.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Right,
+    Up,
+    Left,
+    Down,
+}
+
+impl Direction {
+    // Quarter turns counterclockwise from Right.
+    fn quarters(&self) -> usize {
+        match self {
+            Direction::Right => 0,
+            Direction::Up => 1,
+            Direction::Left => 2,
+            Direction::Down => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Counterclockwise,
+    Clockwise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Spiral {
+    pub start: Direction,
+    pub rotation: Rotation,
+}
+
+// The spiral of day 3, starting to the right and turning counterclockwise.
+impl Default for Spiral {
+    fn default() -> Self {
+        Spiral::new(Direction::Right, Rotation::Counterclockwise)
+    }
+}
+
+// Squares that overflow are too big, so this works up to usize::MAX.
+fn isqrt(n: usize) -> usize {
+    let square = |r: usize| r.checked_mul(r);
+    let mut r = (n as f64).sqrt() as usize;
+    while square(r).is_none_or(|s| s > n) {
+        r -= 1;
+    }
+    while square(r + 1).is_some_and(|s| s <= n) {
+        r += 1;
+    }
+
+    r
+}
+
+// The ring an index is on, ring k is the border of the square from
+// (-k, -k) to (k, k) and ends with index (2k + 1)^2 - 1.
+pub fn ring(n: usize) -> usize {
+    isqrt(n).div_ceil(2)
+}
+
+fn rotate((x, y): Point, quarters: usize) -> Point {
+    match quarters % 4 {
+        0 => (x, y),
+        1 => (-y, x),
+        2 => (-x, -y),
+        _ => (y, -x),
+    }
+}
+
+impl Spiral {
+    pub fn new(start: Direction, rotation: Rotation) -> Self {
+        Spiral { start, rotation }
+    }
+
+    // From the default spiral to this one.
+    fn orient(&self, p: Point) -> Point {
+        let p = match self.rotation {
+            Rotation::Counterclockwise => p,
+            Rotation::Clockwise => (p.0, -p.1),
+        };
+
+        rotate(p, self.start.quarters())
+    }
+
+    // From this spiral to the default one.
+    fn unorient(&self, p: Point) -> Point {
+        let (x, y) = rotate(p, 4 - self.start.quarters());
+
+        match self.rotation {
+            Rotation::Counterclockwise => (x, y),
+            Rotation::Clockwise => (x, -y),
+        }
+    }
+
+    pub fn position(&self, n: usize) -> Point {
+        if n == 0 {
+            return (0, 0);
+        }
+
+        let k = ring(n);
+        let side = 2 * k;
+        let m = n - (side - 1) * (side - 1);
+        let (k, t) = (k as isize, (m % side) as isize);
+
+        let p = match m / side {
+            0 => (k, -k + 1 + t),
+            1 => (k - 1 - t, k),
+            2 => (-k, k - 1 - t),
+            _ => (-k + 1 + t, -k),
+        };
+
+        self.orient(p)
+    }
+
+    pub fn index(&self, p: Point) -> usize {
+        let (x, y) = self.unorient(p);
+        let k = x.abs().max(y.abs());
+        if k == 0 {
+            return 0;
+        }
+
+        let m = if x == k && y > -k {
+            y + k - 1
+        } else if y == k && x < k {
+            2 * k + (k - 1 - x)
+        } else if x == -k && y < k {
+            4 * k + (k - 1 - y)
+        } else {
+            6 * k + (x + k - 1)
+        };
+
+        ((2 * k - 1) * (2 * k - 1) + m) as usize
+    }
+
+    // All positions, starting at the origin.
+    pub fn iter(&self) -> impl Iterator<Item = Point> {
+        let spiral = *self;
+        (0..).map(move |n| spiral.position(n))
+    }
+}
+
+// Numbers the adjacent sums can be computed in. `checked_add` returns None
+// when the sum does not fit, which ends the sequence.
+pub trait Value: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+}
+
+impl Value for u64 {
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u64::checked_add(*self, *other)
+    }
+}
+
+impl Value for u128 {
+    fn zero() -> Self {
+        0
+    }
+
+    fn one() -> Self {
+        1
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u128::checked_add(*self, *other)
+    }
+}
+
+impl Value for BigUint {
+    fn zero() -> Self {
+        BigUint::zero()
+    }
+
+    fn one() -> Self {
+        BigUint::from(1)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self.clone() + other)
+    }
+}
+
+// The origin holds 1 and every further square holds the sum of its
+// neighbours that were already filled in. With eight neighbours this is
+// OEIS A141481, the sequence of day 3 part 2.
+pub struct Sums<T: Value> {
+    spiral: Spiral,
+    connectivity: Connectivity,
+    values: Grid<T>,
+    n: usize,
+    done: bool,
+}
+
+impl<T: Value> Sums<T> {
+    pub fn new(spiral: Spiral, connectivity: Connectivity) -> Self {
+        Sums { spiral, connectivity, values: Grid::sparse(T::zero()), n: 0, done: false }
+    }
+}
+
+impl<T: Value> Iterator for Sums<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.done {
+            return None;
+        }
+
+        let p = self.spiral.position(self.n);
+        let value = if self.n == 0 {
+            Some(T::one())
+        } else {
+            self.values.neighbours(p, self.connectivity)
+                .try_fold(T::zero(), |sum, (_, v)| sum.checked_add(v))
+        };
+
+        match value {
+            Some(value) => {
+                self.values.set(p, value.clone());
+                self.n += 1;
+                Some(value)
+            },
+            None => {
+                self.done = true;
+                None
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ALL: [Spiral; 8] = [
+        Spiral { start: Direction::Right, rotation: Rotation::Counterclockwise },
+        Spiral { start: Direction::Up, rotation: Rotation::Counterclockwise },
+        Spiral { start: Direction::Left, rotation: Rotation::Counterclockwise },
+        Spiral { start: Direction::Down, rotation: Rotation::Counterclockwise },
+        Spiral { start: Direction::Right, rotation: Rotation::Clockwise },
+        Spiral { start: Direction::Up, rotation: Rotation::Clockwise },
+        Spiral { start: Direction::Left, rotation: Rotation::Clockwise },
+        Spiral { start: Direction::Down, rotation: Rotation::Clockwise },
+    ];
+
+    // A fixed xorshift sequence to sample large indices.
+    fn samples(count: usize) -> Vec<usize> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..count)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % (1 << 40)) as usize
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn day3_layout() {
+        let spiral = Spiral::default();
+
+        assert_eq!((1, 0), spiral.position(1));
+        assert_eq!((1, 1), spiral.position(2));
+        assert_eq!((2, 1), spiral.position(11));
+        assert_eq!((0, -2), spiral.position(22));
+        assert_eq!((-15, 16), spiral.position(1023));
+        assert_eq!(1023, spiral.index((-15, 16)));
+    }
+
+    #[test]
+    pub fn square_roots() {
+        let max = usize::MAX;
+        let root = (1usize << (usize::BITS / 2)) - 1;
+
+        assert_eq!(0, isqrt(0));
+        assert_eq!(3, isqrt(15));
+        assert_eq!(4, isqrt(16));
+        assert_eq!(root, isqrt(max));
+        assert_eq!(root - 1, isqrt(root * root - 1));
+        assert_eq!(root, isqrt(root * root));
+        assert_eq!(root.div_ceil(2), ring(max));
+    }
+
+    #[test]
+    pub fn rings() {
+        assert_eq!(0, ring(0));
+        assert_eq!(1, ring(1));
+        assert_eq!(1, ring(8));
+        assert_eq!(2, ring(9));
+        assert_eq!(3, ring(27));
+        assert_eq!(1 << 20, ring(((1 << 21) + 1) * ((1 << 21) + 1) - 1));
+    }
+
+    #[test]
+    pub fn orientations() {
+        let first_steps = ALL.iter().map(|s| s.position(1)).collect::<Vec<_>>();
+        let second_steps = ALL.iter().map(|s| s.position(2)).collect::<Vec<_>>();
+
+        assert_eq!(vec![(1, 0), (0, 1), (-1, 0), (0, -1), (1, 0), (0, 1), (-1, 0), (0, -1)], first_steps);
+        assert_eq!(vec![(1, 1), (-1, 1), (-1, -1), (1, -1), (1, -1), (1, 1), (-1, 1), (-1, -1)], second_steps);
+    }
+
+    #[test]
+    pub fn round_trip() {
+        for spiral in &ALL {
+            for n in (0..20_000).chain(samples(10_000)) {
+                assert_eq!(n, spiral.index(spiral.position(n)), "{:?} {}", spiral, n);
+            }
+
+            for y in -50..=50 {
+                for x in -50..=50 {
+                    assert_eq!((x, y), spiral.position(spiral.index((x, y))));
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn consecutive_positions_are_adjacent() {
+        for spiral in &ALL {
+            let positions = spiral.iter().take(20_000).collect::<Vec<_>>();
+
+            assert!(positions.windows(2).all(|w| (w[0].0 - w[1].0).abs() + (w[0].1 - w[1].1).abs() == 1));
+        }
+
+        for n in samples(1_000) {
+            let (a, b) = (Spiral::default().position(n), Spiral::default().position(n + 1));
+            assert_eq!(1, (a.0 - b.0).abs() + (a.1 - b.1).abs());
+        }
+    }
+
+    #[test]
+    pub fn squares_are_filled_in_order() {
+        // the first (2k + 1)^2 indices cover exactly the square of ring k
+        for spiral in &ALL {
+            for k in 0..20isize {
+                let side = (2 * k + 1) as usize;
+                let mut square = spiral.iter().take(side * side).collect::<Vec<_>>();
+                square.sort();
+
+                let expected = (-k..=k).flat_map(|x| (-k..=k).map(move |y| (x, y))).collect::<Vec<_>>();
+                assert_eq!(expected, square);
+            }
+        }
+    }
+
+    const A141481: [u64; 23] = [
+        1, 1, 2, 4, 5, 10, 11, 23, 25, 26, 54, 57, 59, 122, 133, 142, 147, 304, 330, 351, 362, 747, 806,
+    ];
+
+    #[test]
+    pub fn adjacent_sums() {
+        for spiral in &ALL {
+            let sums = Sums::<u64>::new(*spiral, Connectivity::Eight).take(23).collect::<Vec<_>>();
+            assert_eq!(A141481.to_vec(), sums);
+        }
+    }
+
+    #[test]
+    pub fn adjacent_sums_with_four_neighbours() {
+        let sums = Sums::<u64>::new(Spiral::default(), Connectivity::Four).take(10).collect::<Vec<_>>();
+
+        assert_eq!(vec![1, 1, 1, 2, 2, 3, 3, 4, 5, 5], sums);
+    }
+
+    #[test]
+    pub fn adjacent_sums_beyond_u64() {
+        let small = Sums::<u64>::new(Spiral::default(), Connectivity::Eight).collect::<Vec<_>>();
+        let wide = Sums::<u128>::new(Spiral::default(), Connectivity::Eight).collect::<Vec<_>>();
+        let big = Sums::<BigUint>::new(Spiral::default(), Connectivity::Eight).take(wide.len() + 100).collect::<Vec<_>>();
+
+        // the narrower types stop at the first value they cannot hold
+        assert!(small.len() < wide.len());
+        assert!(small.iter().zip(&wide).all(|(a, b)| *a as u128 == *b));
+        assert!(wide.iter().zip(&big).all(|(a, b)| a.to_string() == b.to_string()));
+        assert!(big[wide.len()].bits() > 128);
+        assert!(big[1..].windows(2).all(|w| w[0] < w[1]));
+    }
+}
@@ -0,0 +1,614 @@
+use super::{Action, Blueprint, Move, Tape, Turing};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Halted { steps: usize },
+    // The configuration after `start + period` steps is the one after
+    // `start` steps moved by `shift` cells. A shift of 0 is a plain cycle.
+    Cycle { start: usize, period: usize, shift: isize },
+    // Neither halted nor detected a cycle within the step limit.
+    Unknown,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Growth {
+    pub leftmost: isize,
+    pub rightmost: isize,
+    pub ones: usize,
+    // Step and width of the visited part of the tape whenever it grew.
+    pub records: Vec<(usize, usize)>,
+}
+
+impl Growth {
+    pub fn width(&self) -> usize {
+        (self.rightmost - self.leftmost + 1) as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis {
+    pub verdict: Verdict,
+    pub growth: Growth,
+}
+
+// Base of the polynomial hash of tape windows, read from the head outward.
+const BASE: u64 = 0x100_0000_01b3;
+
+// The head reached a cell it never visited before. `cells` holds the
+// tape as it was at `step`, from the head outward away from the edge,
+// but only as far as the head went back since then, which is `reach`.
+struct Record {
+    step: usize,
+    state: usize,
+    pos: isize,
+    reach: isize,
+    cells: Vec<u8>,
+    // The hash of `cells` and BASE to the power of its length.
+    hash: u64,
+    power: u64,
+}
+
+impl Record {
+    fn new(turing: &Turing, step: usize, state: usize) -> Self {
+        let pos = turing.pos;
+        let mut record = Record { step, state, pos, reach: pos, cells: vec![], hash: 0, power: 1 };
+        record.push(turing.tape.get(pos));
+
+        record
+    }
+
+    fn push(&mut self, value: u8) {
+        self.cells.push(value);
+        self.hash = self.hash.wrapping_add((value as u64).wrapping_mul(self.power));
+        self.power = self.power.wrapping_mul(BASE);
+    }
+}
+
+// The head is at `pos`. Records that have not seen the head this far back
+// yet copy the cell, which was not touched since they were taken. Their
+// reach only ever grows by one cell at a time, and the newer a record the
+// shorter its reach, so only the newest records need to be looked at.
+fn extend(records: &mut [Record], turing: &Turing, right: bool) {
+    let pos = turing.pos;
+
+    for r in records.iter_mut().rev() {
+        let beyond = if right { pos < r.reach } else { pos > r.reach };
+        if !beyond {
+            break;
+        }
+
+        r.reach = pos;
+        r.push(turing.tape.get(pos));
+    }
+}
+
+// Two records in the same direction with the same state form a
+// translated cycle if the part of the tape the machine looked at in
+// between is the same, relative to the head, at both records. Beyond
+// the head the tape is blank in both cases, so the machine has to
+// repeat what it did in between forever. Windows are compared by their
+// hashes first.
+fn translated(records: &[Record], current: &Record, turing: &Turing, right: bool) -> Option<Verdict> {
+    let back = if right { -1 } else { 1 };
+    let cell = |k: usize| turing.tape.get(current.pos + back * k as isize);
+
+    // hashes of the current tape from the head outward, by length
+    let longest = records.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+    let mut hashes = Vec::with_capacity(longest + 1);
+    let (mut hash, mut power) = (0u64, 1u64);
+    hashes.push(hash);
+    for k in 0..longest {
+        hash = hash.wrapping_add((cell(k) as u64).wrapping_mul(power));
+        power = power.wrapping_mul(BASE);
+        hashes.push(hash);
+    }
+
+    records.iter().rev()
+        .filter(|r| r.state == current.state && r.hash == hashes[r.cells.len()])
+        .find(|r| r.cells.iter().enumerate().all(|(k, v)| *v == cell(k)))
+        .map(|r| Verdict::Cycle { start: r.step, period: current.step - r.step, shift: current.pos - r.pos })
+}
+
+fn mix(mut x: u64) -> u64 {
+    // splitmix64
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+fn value_hash(value: u8) -> u64 {
+    if value == 0 {
+        0
+    } else {
+        mix(value as u64)
+    }
+}
+
+// The inverse of BASE modulo 2^64 by Newton's iteration, BASE is odd.
+const INVERSE: u64 = {
+    let mut inv = BASE;
+    let mut i = 0;
+    while i < 6 {
+        inv = inv.wrapping_mul(2u64.wrapping_sub(BASE.wrapping_mul(inv)));
+        i += 1;
+    }
+
+    inv
+};
+
+// BASE to the power of `exp`, which may be negative.
+fn power(exp: isize) -> u64 {
+    let mut base = if exp < 0 { INVERSE } else { BASE };
+    let mut exp = exp.unsigned_abs();
+    let mut result = 1u64;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exp >>= 1;
+    }
+
+    result
+}
+
+// A machine together with a hash of its configuration that is updated
+// with every step. Like configurations are compared relative to the
+// first non-blank cell of the tape, or to the head on a blank tape, the
+// hash does not change when everything is moved.
+struct Hashed<'a> {
+    turing: Turing<'a>,
+    // The sum of the hashes of the non-blank cells times BASE^pos.
+    tape: u64,
+    // BASE^pos for the head.
+    head: u64,
+    first: Option<isize>,
+    // BASE^-first.
+    unshift: u64,
+    ones: usize,
+}
+
+impl<'a> Hashed<'a> {
+    fn new(blueprint: &'a Blueprint) -> Self {
+        Hashed { turing: Turing::new(blueprint), tape: 0, head: 1, first: None, unshift: 1, ones: 0 }
+    }
+
+    fn tick(&mut self) {
+        let pos = self.turing.pos;
+        let before = self.turing.tape.get(pos);
+        self.turing.tick();
+        let after = self.turing.tape.get(pos);
+
+        if before != after {
+            let change = value_hash(after).wrapping_sub(value_hash(before));
+            self.tape = self.tape.wrapping_add(change.wrapping_mul(self.head));
+
+            if before == 0 {
+                self.ones += 1;
+                if self.first.is_none_or(|f| pos < f) {
+                    self.first = Some(pos);
+                }
+            } else if after == 0 {
+                self.ones -= 1;
+                if self.first == Some(pos) {
+                    self.first = (self.ones > 0).then(|| {
+                        (pos + 1..).find(|p| self.turing.tape.get(*p) != 0).unwrap()
+                    });
+                }
+            }
+
+            if let Some(f) = self.first {
+                self.unshift = power(-f);
+            }
+        }
+
+        self.head = match self.turing.pos - pos {
+            1 => self.head.wrapping_mul(BASE),
+            _ => self.head.wrapping_mul(INVERSE),
+        };
+    }
+
+    // The first non-blank cell, or the head on a blank tape.
+    fn reference(&self) -> isize {
+        self.first.unwrap_or(self.turing.pos)
+    }
+
+    fn key(&self) -> u64 {
+        let state = self.turing.state.map(|s| s as u64 + 1).unwrap_or(0);
+        let offset = (self.turing.pos - self.reference()) as u64;
+
+        self.tape.wrapping_mul(self.unshift) ^ mix(state << 48 ^ offset)
+    }
+}
+
+// A configuration to compare against, taken after `step` steps.
+struct Snapshot {
+    step: usize,
+    key: u64,
+    state: Option<usize>,
+    offset: isize,
+    reference: isize,
+    tape: Tape,
+}
+
+impl Snapshot {
+    fn take(machine: &Hashed, step: usize) -> Self {
+        let turing = &machine.turing;
+        let reference = machine.reference();
+
+        Snapshot {
+            step,
+            key: machine.key(),
+            state: turing.state,
+            offset: turing.pos - reference,
+            reference,
+            tape: turing.tape.clone(),
+        }
+    }
+
+    // The shift between the snapshot and the machine if both are in the
+    // same configuration relative to their reference.
+    fn matches(&self, machine: &Hashed) -> Option<isize> {
+        let turing = &machine.turing;
+        let reference = machine.reference();
+        let cells = |tape: &'_ Tape| tape.content().map(|(_, cells)| cells.to_vec()).unwrap_or_default();
+
+        let same = machine.key() == self.key
+            && turing.state == self.state
+            && turing.pos - reference == self.offset
+            && cells(&turing.tape) == cells(&self.tape);
+
+        same.then_some(reference - self.reference)
+    }
+}
+
+// Brent's cycle detection, comparing against a single configuration
+// taken after a power of two steps.
+struct Brent {
+    snapshot: Snapshot,
+    power: usize,
+}
+
+impl Brent {
+    fn new(machine: &Hashed) -> Self {
+        Brent { snapshot: Snapshot::take(machine, 0), power: 1 }
+    }
+
+    // The period once the machine is in a configuration it was in before.
+    // That is found within three times the steps until the configuration
+    // repeats for the first time.
+    fn check(&mut self, machine: &Hashed, step: usize) -> Option<usize> {
+        let distance = step - self.snapshot.step;
+        if distance == 0 {
+            return None;
+        }
+
+        if self.snapshot.matches(machine).is_some() {
+            return Some(distance);
+        }
+
+        if distance == self.power {
+            self.snapshot = Snapshot::take(machine, step);
+            self.power *= 2;
+        }
+
+        None
+    }
+}
+
+// The cycle with the given period that starts first and the step at
+// which its configuration is seen again: runs one machine `period` steps
+// ahead of another until both are in the same configuration.
+fn repeated(blueprint: &Blueprint, period: usize) -> (Verdict, usize) {
+    let mut behind = Hashed::new(blueprint);
+    let mut ahead = Hashed::new(blueprint);
+    for _ in 0..period {
+        ahead.tick();
+    }
+
+    let mut start = 0;
+    loop {
+        if let Some(shift) = Snapshot::take(&behind, start).matches(&ahead) {
+            return (Verdict::Cycle { start, period, shift }, start + period);
+        }
+
+        behind.tick();
+        ahead.tick();
+        start += 1;
+    }
+}
+
+// Runs the machine for at most `limit` steps and checks whether it
+// halts or repeats itself. A configuration that repeats, relative to the
+// tape content, is reported before a translated cycle found at the same
+// step or later, and the growth is the one up to where it was seen.
+pub fn analyse(blueprint: &Blueprint, limit: usize) -> Analysis {
+    let mut machine = Hashed::new(blueprint);
+    let mut brent = Brent::new(&machine);
+    let mut growth = Growth::default();
+    // the leftmost and rightmost cell for every entry in `growth.records`
+    let mut extents = vec![];
+    let mut left: Vec<Record> = vec![];
+    let mut right: Vec<Record> = vec![];
+
+    let mut verdict = Verdict::Unknown;
+    let mut ones = None;
+    let mut repeat = None;
+    for step in 0..=limit {
+        let turing = &machine.turing;
+        let state = match turing.state {
+            Some(s) => s,
+            None => {
+                verdict = Verdict::Halted { steps: step };
+                break;
+            },
+        };
+
+        if let Some(period) = brent.check(&machine, step) {
+            repeat = Some(repeated(blueprint, period));
+            break;
+        }
+
+        extend(&mut left, turing, false);
+        extend(&mut right, turing, true);
+
+        if turing.pos > growth.rightmost || turing.pos < growth.leftmost {
+            growth.leftmost = growth.leftmost.min(turing.pos);
+            growth.rightmost = growth.rightmost.max(turing.pos);
+            growth.records.push((step, growth.width()));
+            extents.push((growth.leftmost, growth.rightmost));
+
+            let record = Record::new(turing, step, state);
+            let to_right = record.pos == growth.rightmost;
+            let records = if to_right { &mut right } else { &mut left };
+
+            if let Some(v) = translated(records, &record, turing, to_right) {
+                verdict = v;
+                ones = Some(turing.checksum());
+
+                // Brent's algorithm would have found any configuration
+                // repeated by now within three times the steps
+                for later in step + 1..=3 * step + 3 {
+                    machine.tick();
+                    if let Some(period) = brent.check(&machine, later) {
+                        repeat = Some(repeated(blueprint, period)).filter(|(_, seen)| *seen <= step);
+                        break;
+                    }
+                }
+                break;
+            }
+            records.push(record);
+        }
+
+        if step == limit {
+            break;
+        }
+
+        machine.tick();
+    }
+
+    growth.ones = match repeat {
+        Some((cycle, seen)) => {
+            verdict = cycle;
+
+            let kept = growth.records.iter().take_while(|(step, _)| *step < seen).count();
+            growth.records.truncate(kept);
+            (growth.leftmost, growth.rightmost) = extents[..kept].last().cloned().unwrap_or((0, 0));
+
+            let mut turing = Turing::new(blueprint);
+            turing.run(seen, false);
+            turing.checksum()
+        },
+        None => ones.unwrap_or_else(|| machine.turing.checksum()),
+    };
+
+    Analysis { verdict, growth }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Champion {
+    pub steps: usize,
+    pub ones: usize,
+    pub blueprint: Blueprint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusyBeaver {
+    // The halting machines with the most steps and the most ones.
+    pub steps: Champion,
+    pub ones: Champion,
+    // The number of halting machines found.
+    pub halting: usize,
+}
+
+// Undefined transitions halt, writing a one.
+fn complete(partial: &[[Option<Action>; 2]]) -> Blueprint {
+    let halt = Action::new(1, Move::Right, None);
+    let table = partial.iter()
+        .map(|t| [t[0].unwrap_or(halt), t[1].unwrap_or(halt)])
+        .collect::<Vec<_>>();
+
+    Blueprint::new(0, 0, table)
+}
+
+fn explore(partial: &mut [[Option<Action>; 2]], limit: usize, result: &mut Option<BusyBeaver>) {
+    let blueprint = complete(partial);
+    let mut turing = Turing::new(&blueprint);
+
+    let mut steps = 0;
+    let mut last = None;
+    while steps < limit && !turing.halted() {
+        last = turing.transition();
+        turing.tick();
+        steps += 1;
+    }
+
+    let (state, value) = match (turing.halted(), last) {
+        (true, Some(t)) => t,
+        _ => return,
+    };
+
+    let champion = Champion { steps, ones: turing.checksum(), blueprint: blueprint.clone() };
+    match result {
+        None => *result = Some(BusyBeaver { steps: champion.clone(), ones: champion, halting: 1 }),
+        Some(bb) => {
+            bb.halting += 1;
+            if champion.steps > bb.steps.steps {
+                bb.steps = champion.clone();
+            }
+            if champion.ones > bb.ones.ones {
+                bb.ones = champion;
+            }
+        },
+    }
+
+    // keep at least one transition undefined so the machine can halt
+    let defined = partial.iter().flatten().filter(|a| a.is_some()).count();
+    if defined + 1 >= partial.len() * 2 {
+        return;
+    }
+
+    // states are introduced in order to skip renamed copies of machines
+    let used = partial.iter().flatten().flatten()
+        .filter_map(|a| a.next)
+        .chain(std::iter::once(state))
+        .max()
+        .unwrap_or(0) + 1;
+    let next = used.min(partial.len() - 1);
+
+    for n in 0..=next {
+        for write in 0..2 {
+            for movement in &[Move::Left, Move::Right] {
+                partial[state][value as usize] = Some(Action::new(write, *movement, Some(n)));
+                explore(partial, limit, result);
+            }
+        }
+    }
+
+    partial[state][value as usize] = None;
+}
+
+// Enumerates all machines with the given number of states and two
+// symbols in tree normal form, i.e. transitions are only defined once
+// a machine needs them. Machines running for more than `limit` steps
+// are considered to never halt.
+pub fn busy_beaver(states: usize, limit: usize) -> Option<BusyBeaver> {
+    if states == 0 {
+        return None;
+    }
+
+    let mut partial = vec![[None, None]; states];
+    let mut result = None;
+
+    explore(&mut partial, limit, &mut result);
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn halting() {
+        let blueprint = Blueprint::new(0, 0, vec![
+            [Action::new(1, Move::Right, Some(1)), Action::new(1, Move::Left, Some(1))],
+            [Action::new(1, Move::Left, Some(0)), Action::new(1, Move::Right, None)],
+        ]);
+
+        let analysis = analyse(&blueprint, 100);
+
+        assert_eq!(Verdict::Halted { steps: 6 }, analysis.verdict);
+        assert_eq!(4, analysis.growth.ones);
+        assert_eq!(4, analysis.growth.width());
+    }
+
+    #[test]
+    pub fn plain_cycle() {
+        let blueprint = Blueprint::new(0, 0, vec![
+            [Action::new(0, Move::Right, Some(1)), Action::new(0, Move::Right, Some(1))],
+            [Action::new(0, Move::Left, Some(0)), Action::new(0, Move::Left, Some(0))],
+        ]);
+
+        assert_eq!(Verdict::Cycle { start: 0, period: 2, shift: 0 }, analyse(&blueprint, 100).verdict);
+    }
+
+    #[test]
+    pub fn plain_cycle_after_a_prefix() {
+        // writes a single 1 and then bounces between it and the blank on its right
+        let blueprint = Blueprint::new(0, 0, vec![
+            [Action::new(1, Move::Right, Some(1)), Action::new(1, Move::Right, Some(1))],
+            [Action::new(0, Move::Left, Some(2)), Action::new(1, Move::Left, Some(2))],
+            [Action::new(0, Move::Right, Some(1)), Action::new(1, Move::Right, Some(1))],
+        ]);
+
+        let analysis = analyse(&blueprint, 100);
+
+        assert_eq!(Verdict::Cycle { start: 1, period: 2, shift: 0 }, analysis.verdict);
+        assert_eq!(1, analysis.growth.ones);
+    }
+
+    #[test]
+    pub fn long_run_without_cycle() {
+        // sweeps over its ones, adding one more at either end
+        let blueprint = Blueprint::new(0, 0, vec![
+            [Action::new(1, Move::Left, Some(1)), Action::new(1, Move::Right, Some(0))],
+            [Action::new(1, Move::Right, Some(0)), Action::new(1, Move::Left, Some(1))],
+        ]);
+
+        let analysis = analyse(&blueprint, 1_000_000);
+        let mut turing = Turing::new(&blueprint);
+        turing.run(1_000_000, false);
+
+        assert_eq!(Verdict::Unknown, analysis.verdict);
+        assert_eq!(turing.checksum(), analysis.growth.ones);
+        assert_eq!(turing.checksum(), analysis.growth.width());
+    }
+
+    #[test]
+    pub fn translated_cycle_leaving_a_trail() {
+        // writes 1 0 1 0 ... while moving right
+        let blueprint = Blueprint::new(0, 0, vec![
+            [Action::new(1, Move::Right, Some(1)), Action::new(1, Move::Right, Some(1))],
+            [Action::new(0, Move::Right, Some(0)), Action::new(0, Move::Right, Some(0))],
+        ]);
+
+        let analysis = analyse(&blueprint, 100);
+
+        assert_eq!(Verdict::Cycle { start: 1, period: 2, shift: 2 }, analysis.verdict);
+    }
+
+    #[test]
+    pub fn translated_cycle_moving_back_and_forth() {
+        // A: 0 -> 1 R B, B: 0 -> 0 L C, C: 1 -> 1 R D, D: 0 -> 1 R A
+        let blueprint = Blueprint::new(0, 0, vec![
+            [Action::new(1, Move::Right, Some(1)), Action::new(1, Move::Right, Some(1))],
+            [Action::new(0, Move::Left, Some(2)), Action::new(0, Move::Left, Some(2))],
+            [Action::new(1, Move::Right, Some(3)), Action::new(1, Move::Right, Some(3))],
+            [Action::new(1, Move::Right, Some(0)), Action::new(1, Move::Right, Some(0))],
+        ]);
+
+        let analysis = analyse(&blueprint, 100);
+
+        assert_eq!(Verdict::Cycle { start: 1, period: 4, shift: 2 }, analysis.verdict);
+        assert_eq!(vec![(1, 2), (4, 3), (5, 4)], analysis.growth.records);
+    }
+
+    #[test]
+    pub fn busy_beaver_2() {
+        let bb = busy_beaver(2, 100).unwrap();
+
+        assert_eq!(6, bb.steps.steps);
+        assert_eq!(4, bb.ones.ones);
+        assert_eq!(Verdict::Halted { steps: 6 }, analyse(&bb.steps.blueprint, 100).verdict);
+    }
+
+    #[test]
+    pub fn busy_beaver_3() {
+        let bb = busy_beaver(3, 100).unwrap();
+
+        assert_eq!(21, bb.steps.steps);
+        assert_eq!(6, bb.ones.ones);
+    }
+}
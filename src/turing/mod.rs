@@ -0,0 +1,452 @@
+use std::cmp::max;
+use std::fmt::{Display, Formatter, Error};
+use std::str::FromStr;
+use regex::Regex;
+use crate::utils::ParseError;
+
+pub mod analysis;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Left,
+    Right,
+}
+
+impl Move {
+    pub fn delta(self) -> isize {
+        match self {
+            Move::Left => -1,
+            Move::Right => 1,
+        }
+    }
+}
+
+// A transition. The machine halts if there is no next state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Action {
+    pub write: u8,
+    pub movement: Move,
+    pub next: Option<usize>,
+}
+
+impl Action {
+    pub fn new(write: u8, movement: Move, next: Option<usize>) -> Self {
+        Action { write, movement, next }
+    }
+}
+
+// States are referred to by their index in `names` and `table`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blueprint {
+    pub start: usize,
+    pub steps: usize,
+    pub names: Vec<String>,
+    pub table: Vec<[Action; 2]>,
+}
+
+impl Blueprint {
+    // Creates a blueprint at runtime, states are named A, B, C, ...
+    pub fn new(start: usize, steps: usize, table: Vec<[Action; 2]>) -> Self {
+        let names = (0..table.len())
+            .map(|i| ((b'A' + i as u8) as char).to_string())
+            .collect::<Vec<_>>();
+
+        Blueprint { start, steps, names, table }
+    }
+}
+
+fn parse_action(write: &str, movement: &str) -> Result<(u8, Move), ParseError> {
+    let write = write.parse::<u8>()?;
+    let movement = match movement {
+        "left" => Move::Left,
+        "right" => Move::Right,
+        _ => Err(ParseError::new("Invalid direction"))?,
+    };
+
+    Ok((write, movement))
+}
+
+impl FromStr for Blueprint {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        lazy_static!{
+            static ref HEADER: Regex = Regex::new(r"Begin in state (?P<start>\w+)\.\s+Perform a diagnostic checksum after (?P<steps>\d+) steps\.").unwrap();
+            static ref STATE: Regex = Regex::new(concat!(
+                r"In state (?P<state>\w+):\s+",
+                r"If the current value is 0:\s+",
+                r"- Write the value (?P<w0>[01])\.\s+",
+                r"- Move one slot to the (?P<m0>left|right)\.\s+",
+                r"- (?:Continue with state (?P<n0>\w+)|Halt)\.\s+",
+                r"If the current value is 1:\s+",
+                r"- Write the value (?P<w1>[01])\.\s+",
+                r"- Move one slot to the (?P<m1>left|right)\.\s+",
+                r"- (?:Continue with state (?P<n1>\w+)|Halt)\.",
+            )).unwrap();
+        }
+
+        let header = HEADER.captures(s).ok_or(ParseError::new("Could not parse header"))?;
+        let steps = header["steps"].parse::<usize>()?;
+
+        let states = STATE.captures_iter(s).collect::<Vec<_>>();
+        if states.len() != s.matches("In state").count() {
+            Err(ParseError::new("Could not parse state"))?;
+        }
+
+        let names = states.iter()
+            .map(|c| c["state"].to_string())
+            .collect::<Vec<_>>();
+        let index = |name: &str| names.iter()
+            .position(|n| n == name)
+            .ok_or(ParseError::new(&format!("Unknown state {}", name)));
+        let next = |name: Option<regex::Match>| name.map(|n| index(n.as_str())).transpose();
+
+        let start = index(&header["start"])?;
+        let mut table = vec![];
+        for c in &states {
+            let (w0, m0) = parse_action(&c["w0"], &c["m0"])?;
+            let (w1, m1) = parse_action(&c["w1"], &c["m1"])?;
+
+            table.push([
+                Action::new(w0, m0, next(c.name("n0"))?),
+                Action::new(w1, m1, next(c.name("n1"))?),
+            ]);
+        }
+
+        Ok(Blueprint { start, steps, names, table })
+    }
+}
+
+// Writes the blueprint in the same format it is parsed from, halting
+// transitions are written as `- Halt.`.
+impl Display for Blueprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        writeln!(f, "Begin in state {}.", self.names[self.start])?;
+        writeln!(f, "Perform a diagnostic checksum after {} steps.", self.steps)?;
+
+        for (name, actions) in self.names.iter().zip(&self.table) {
+            writeln!(f)?;
+            writeln!(f, "In state {}:", name)?;
+
+            for (value, action) in actions.iter().enumerate() {
+                let movement = match action.movement {
+                    Move::Left => "left",
+                    Move::Right => "right",
+                };
+
+                writeln!(f, "  If the current value is {}:", value)?;
+                writeln!(f, "    - Write the value {}.", action.write)?;
+                writeln!(f, "    - Move one slot to the {}.", movement)?;
+                match action.next {
+                    Some(n) => writeln!(f, "    - Continue with state {}.", self.names[n])?,
+                    None => writeln!(f, "    - Halt.")?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// A tape that grows in both directions. Cell `pos` is stored at
+// `cells[origin + pos]`, everything outside of `cells` is 0.
+#[derive(Debug, Clone, Default)]
+pub struct Tape {
+    cells: Vec<u8>,
+    origin: usize,
+}
+
+impl Tape {
+    fn offset(&self, pos: isize) -> isize {
+        pos + self.origin as isize
+    }
+
+    pub fn contains(&self, pos: isize) -> bool {
+        let i = self.offset(pos);
+        i >= 0 && (i as usize) < self.cells.len()
+    }
+
+    pub fn get(&self, pos: isize) -> u8 {
+        if self.contains(pos) {
+            self.cells[self.offset(pos) as usize]
+        } else {
+            0
+        }
+    }
+
+    // Makes sure `pos` is backed by memory, at least doubling the tape
+    // in the direction it has to grow.
+    fn grow(&mut self, pos: isize) {
+        let i = self.offset(pos);
+
+        if i < 0 {
+            let extra = max((-i) as usize, max(self.cells.len(), 16));
+            let mut cells = vec![0; extra];
+            cells.extend_from_slice(&self.cells);
+            self.cells = cells;
+            self.origin += extra;
+        } else if i as usize >= self.cells.len() {
+            let len = max(i as usize + 1, max(self.cells.len() * 2, 16));
+            self.cells.resize(len, 0);
+        }
+    }
+
+    pub fn set(&mut self, pos: isize, val: u8) {
+        self.grow(pos);
+
+        let i = self.offset(pos) as usize;
+        self.cells[i] = val;
+    }
+
+    // Whether `pos` and every cell further in the direction of `movement`
+    // lie outside the memory backing the tape and so are all 0.
+    pub fn blank_beyond(&self, pos: isize, movement: Move) -> bool {
+        let i = self.offset(pos);
+
+        match movement {
+            Move::Left => i < 0,
+            Move::Right => i >= self.cells.len() as isize,
+        }
+    }
+
+    pub fn ones(&self) -> usize {
+        self.cells.iter().filter(|v| **v == 1).count()
+    }
+
+    // The non-blank part of the tape and the position of its first cell.
+    pub fn content(&self) -> Option<(isize, &[u8])> {
+        let first = self.cells.iter().position(|v| *v != 0)?;
+        let last = self.cells.iter().rposition(|v| *v != 0)?;
+
+        Some((first as isize - self.origin as isize, &self.cells[first..=last]))
+    }
+}
+
+pub struct Turing<'a> {
+    pub blueprint: &'a Blueprint,
+    pub state: Option<usize>,
+    pub tape: Tape,
+    pub pos: isize,
+}
+
+impl<'a> Turing<'a> {
+    pub fn new(blueprint: &'a Blueprint) -> Self {
+        let state = Some(blueprint.start);
+        let tape = Tape::default();
+        let pos = 0;
+
+        Self { blueprint, state, tape, pos }
+    }
+
+    pub fn halted(&self) -> bool {
+        self.state.is_none()
+    }
+
+    // The transition the machine executes next.
+    pub fn transition(&self) -> Option<(usize, u8)> {
+        self.state.map(|s| (s, self.tape.get(self.pos)))
+    }
+
+    pub fn tick(&mut self) {
+        let (state, current) = match self.transition() {
+            Some(t) => t,
+            None => return,
+        };
+        let action = self.blueprint.table[state][current as usize];
+
+        self.tape.set(self.pos, action.write);
+        self.pos += action.movement.delta();
+        self.state = action.next;
+    }
+
+    // Executes up to `limit` steps at once if the current transition
+    // keeps the state and moves over a run of equal cells. Returns the
+    // number of steps executed.
+    pub fn macro_tick(&mut self, limit: usize) -> usize {
+        let (state, current) = match self.transition() {
+            Some(t) => t,
+            None => return 0,
+        };
+        let action = self.blueprint.table[state][current as usize];
+
+        if action.next != self.state {
+            self.tick();
+            return 1;
+        }
+
+        let delta = action.movement.delta();
+        let mut steps = 0;
+        while steps < limit && self.tape.get(self.pos) == current {
+            // blank tape that is left blank can be skipped entirely, as
+            // long as the head moves away from the written part
+            if current == 0 && action.write == 0 && self.tape.blank_beyond(self.pos, action.movement) {
+                self.pos += delta * (limit - steps) as isize;
+                return limit;
+            }
+
+            self.tape.set(self.pos, action.write);
+            self.pos += delta;
+            steps += 1;
+        }
+
+        steps
+    }
+
+    // Runs for the given number of steps or until the machine halts.
+    // Returns the number of steps executed.
+    pub fn run(&mut self, steps: usize, macro_steps: bool) -> usize {
+        let mut done = 0;
+
+        while done < steps && !self.halted() {
+            done += if macro_steps {
+                self.macro_tick(steps - done)
+            } else {
+                self.tick();
+                1
+            };
+        }
+
+        done
+    }
+
+    pub fn checksum(&self) -> usize {
+        self.tape.ones()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../day25/example");
+
+    #[test]
+    pub fn parse_example() {
+        let blueprint = Blueprint::from_str(EXAMPLE).unwrap();
+
+        assert_eq!(0, blueprint.start);
+        assert_eq!(6, blueprint.steps);
+        assert_eq!(vec!["A", "B"], blueprint.names);
+        assert_eq!(Action::new(0, Move::Left, Some(1)), blueprint.table[0][1]);
+    }
+
+    #[test]
+    pub fn parse_unknown_state() {
+        let input = EXAMPLE.replace("Continue with state A", "Continue with state C");
+
+        assert!(Blueprint::from_str(&input).is_err());
+    }
+
+    #[test]
+    pub fn display_roundtrips() {
+        let blueprint = Blueprint::from_str(EXAMPLE).unwrap();
+        assert_eq!(EXAMPLE, blueprint.to_string());
+
+        let halting = EXAMPLE.replacen("Continue with state A", "Halt", 1);
+        let blueprint = Blueprint::from_str(&halting).unwrap();
+        assert_eq!(None, blueprint.table[1][0].next);
+        assert_eq!(halting, blueprint.to_string());
+    }
+
+    #[test]
+    pub fn tape_grows_in_both_directions() {
+        let mut tape = Tape::default();
+
+        tape.set(-20, 1);
+        tape.set(40, 1);
+        tape.set(0, 1);
+
+        assert_eq!(1, tape.get(-20));
+        assert_eq!(0, tape.get(-19));
+        assert_eq!(1, tape.get(0));
+        assert_eq!(1, tape.get(40));
+        assert_eq!(0, tape.get(-1000));
+        assert_eq!(3, tape.ones());
+        assert_eq!(Some(-20), tape.content().map(|(start, _)| start));
+        assert_eq!(Some(61), tape.content().map(|(_, cells)| cells.len()));
+    }
+
+    #[test]
+    pub fn macro_steps_match_single_steps() {
+        // B keeps sweeping left over ones
+        let input = EXAMPLE
+            .replacen("Write the value 1.\n    - Move one slot to the right.\n    - Continue with state A.",
+                      "Write the value 1.\n    - Move one slot to the left.\n    - Continue with state B.", 1);
+        let blueprint = Blueprint::from_str(&input).unwrap();
+        assert_eq!(Some(1), blueprint.table[1][1].next);
+
+        for steps in 0..200 {
+            let mut single = Turing::new(&blueprint);
+            let mut fast = Turing::new(&blueprint);
+            single.run(steps, false);
+            fast.run(steps, true);
+
+            assert_eq!(single.pos, fast.pos);
+            assert_eq!(single.state, fast.state);
+            assert_eq!(single.checksum(), fast.checksum());
+        }
+    }
+
+    #[test]
+    pub fn macro_steps_turning_back_into_written_tape() {
+        // steps left off the tape and then sweeps back right over it
+        let blueprint = Blueprint::new(0, 100, vec![
+            [Action::new(1, Move::Left, Some(1)), Action::new(1, Move::Left, Some(1))],
+            [Action::new(0, Move::Right, Some(1)), Action::new(1, Move::Right, None)],
+        ]);
+
+        for steps in 0..=blueprint.steps {
+            let mut single = Turing::new(&blueprint);
+            let mut fast = Turing::new(&blueprint);
+
+            assert_eq!(single.run(steps, false), fast.run(steps, true));
+            assert_eq!(single.pos, fast.pos);
+            assert_eq!(single.state, fast.state);
+            assert_eq!(single.checksum(), fast.checksum());
+        }
+
+        let mut fast = Turing::new(&blueprint);
+        assert_eq!(3, fast.run(blueprint.steps, true));
+        assert!(fast.halted());
+        assert_eq!(1, fast.pos);
+    }
+
+    #[test]
+    pub fn blank_beyond_the_tape() {
+        let mut tape = Tape::default();
+        tape.set(0, 1);
+
+        assert!(tape.blank_beyond(-1, Move::Left));
+        assert!(!tape.blank_beyond(-1, Move::Right));
+        assert!(!tape.blank_beyond(0, Move::Left));
+        assert!(tape.blank_beyond(16, Move::Right));
+        assert!(!tape.blank_beyond(16, Move::Left));
+    }
+
+    #[test]
+    pub fn macro_steps_skip_blank_tape() {
+        let blueprint = Blueprint::new(0, 1_000_000_000_000, vec![[
+            Action::new(0, Move::Left, Some(0)),
+            Action::new(1, Move::Left, Some(0)),
+        ]]);
+        let mut turing = Turing::new(&blueprint);
+
+        turing.run(blueprint.steps, true);
+
+        assert_eq!(-1_000_000_000_000, turing.pos);
+    }
+
+    #[test]
+    pub fn run_stops_when_halted() {
+        let blueprint = Blueprint::new(0, 10, vec![[
+            Action::new(1, Move::Right, Some(0)),
+            Action::new(1, Move::Right, None),
+        ]]);
+        let mut turing = Turing::new(&blueprint);
+        turing.tape.set(3, 1);
+
+        assert_eq!(4, turing.run(blueprint.steps, false));
+        assert!(turing.halted());
+        assert_eq!(4, turing.checksum());
+    }
+}
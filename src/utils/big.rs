@@ -0,0 +1,138 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, AddAssign};
+
+// An unsigned integer of arbitrary size, only as much as sequences of
+// sums need. Limbs are stored least significant first without trailing
+// zero limbs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+  limbs: Vec<u32>,
+}
+
+impl BigUint {
+  pub fn zero() -> Self {
+    BigUint::default()
+  }
+
+  pub fn bits(&self) -> usize {
+    match self.limbs.last() {
+      Some(l) => self.limbs.len() * 32 - l.leading_zeros() as usize,
+      None => 0,
+    }
+  }
+}
+
+impl From<u64> for BigUint {
+  fn from(value: u64) -> Self {
+    let mut limbs = vec![value as u32, (value >> 32) as u32];
+    while limbs.last() == Some(&0) {
+      limbs.pop();
+    }
+
+    BigUint { limbs }
+  }
+}
+
+impl<'a> AddAssign<&'a BigUint> for BigUint {
+  fn add_assign(&mut self, other: &'a BigUint) {
+    if self.limbs.len() < other.limbs.len() {
+      self.limbs.resize(other.limbs.len(), 0);
+    }
+
+    let mut carry = 0u64;
+    for (i, limb) in self.limbs.iter_mut().enumerate() {
+      let sum = *limb as u64 + *other.limbs.get(i).unwrap_or(&0) as u64 + carry;
+      *limb = sum as u32;
+      carry = sum >> 32;
+    }
+
+    if carry > 0 {
+      self.limbs.push(carry as u32);
+    }
+  }
+}
+
+impl<'a> Add<&'a BigUint> for BigUint {
+  type Output = BigUint;
+
+  fn add(mut self, other: &'a BigUint) -> BigUint {
+    self += other;
+    self
+  }
+}
+
+impl Ord for BigUint {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.limbs.len().cmp(&other.limbs.len())
+      .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+  }
+}
+
+impl PartialOrd for BigUint {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl fmt::Display for BigUint {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    const BASE: u64 = 1_000_000_000;
+
+    // repeatedly divide by 10^9, collecting nine digits at a time
+    let mut limbs = self.limbs.clone();
+    let mut chunks = vec![];
+    while !limbs.is_empty() {
+      let mut rem = 0u64;
+      for limb in limbs.iter_mut().rev() {
+        let cur = (rem << 32) | *limb as u64;
+        *limb = (cur / BASE) as u32;
+        rem = cur % BASE;
+      }
+
+      chunks.push(rem);
+      while limbs.last() == Some(&0) {
+        limbs.pop();
+      }
+    }
+
+    match chunks.split_last() {
+      Some((first, rest)) => {
+        write!(f, "{}", first)?;
+        for c in rest.iter().rev() {
+          write!(f, "{:09}", c)?;
+        }
+        Ok(())
+      },
+      None => write!(f, "0"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  pub fn add_with_carry() {
+    let a = BigUint::from(u64::MAX);
+    let b = a.clone() + &BigUint::from(1);
+
+    assert_eq!("18446744073709551615", a.to_string());
+    assert_eq!("18446744073709551616", b.to_string());
+    assert_eq!(65, b.bits());
+    assert!(b > a);
+    assert_eq!("0", BigUint::zero().to_string());
+  }
+
+  #[test]
+  pub fn doubling() {
+    let mut v = BigUint::from(1);
+    for _ in 0..200 {
+      v += &v.clone();
+    }
+
+    assert_eq!("1606938044258990275541962092341162602522202993782792835301376", v.to_string());
+    assert_eq!(201, v.bits());
+  }
+}
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::fmt;
+use super::ParseError;
+
+pub type Point = (isize, isize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+  Four,
+  Eight,
+}
+
+impl Connectivity {
+  pub fn deltas(&self) -> &'static [Point] {
+    match self {
+      Connectivity::Four => &[(0, -1), (1, 0), (0, 1), (-1, 0)],
+      Connectivity::Eight => &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)],
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Backing<T> {
+  // A rectangle of cells starting at `origin`, row by row.
+  Dense { origin: Point, width: usize, height: usize, cells: Vec<T> },
+  // Only the cells that were set.
+  Sparse(HashMap<Point, T>),
+}
+
+// A 2D grid of cells, y grows downwards. Cells that are not stored read
+// as `default`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+  backing: Backing<T>,
+  default: T,
+}
+
+impl<T: Clone> Grid<T> {
+  pub fn dense(width: usize, height: usize, default: T) -> Self {
+    let cells = vec![default.clone(); width * height];
+    Grid { backing: Backing::Dense { origin: (0, 0), width, height, cells }, default }
+  }
+
+  pub fn sparse(default: T) -> Self {
+    Grid { backing: Backing::Sparse(HashMap::new()), default }
+  }
+
+  // Parses one row per line into a dense grid. Short lines are padded
+  // with `default`.
+  pub fn parse<F>(s: &str, default: T, cell: F) -> Result<Self, ParseError>
+    where F: Fn(char) -> Result<T, ParseError> {
+
+    let lines = s.lines().collect::<Vec<_>>();
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let mut grid = Grid::dense(width, lines.len(), default);
+
+    for (y, l) in lines.iter().enumerate() {
+      for (x, c) in l.chars().enumerate() {
+        grid.set((x as isize, y as isize), cell(c)?);
+      }
+    }
+
+    Ok(grid)
+  }
+
+  // Moves the grid so that its top left corner is at `origin`.
+  pub fn with_origin(mut self, origin: Point) -> Self {
+    let offset = match self.bounds() {
+      Some((min, _)) => (origin.0 - min.0, origin.1 - min.1),
+      None => (0, 0),
+    };
+
+    self.backing = match self.backing {
+      Backing::Dense { width, height, cells, .. } => Backing::Dense { origin, width, height, cells },
+      Backing::Sparse(cells) => Backing::Sparse(cells.into_iter()
+        .map(|(p, v)| ((p.0 + offset.0, p.1 + offset.1), v))
+        .collect()),
+    };
+
+    self
+  }
+
+  pub fn to_sparse(&self) -> Self {
+    let cells = self.iter().map(|(p, v)| (p, v.clone())).collect();
+    Grid { backing: Backing::Sparse(cells), default: self.default.clone() }
+  }
+
+  pub fn is_dense(&self) -> bool {
+    matches!(self.backing, Backing::Dense { .. })
+  }
+
+  // The origin, width and cells row by row of a dense grid.
+  pub fn dense_mut(&mut self) -> Option<(Point, usize, &mut [T])> {
+    match &mut self.backing {
+      Backing::Dense { origin, width, cells, .. } => Some((*origin, *width, cells)),
+      Backing::Sparse(_) => None,
+    }
+  }
+
+  fn index(&self, p: Point) -> Option<usize> {
+    match &self.backing {
+      Backing::Dense { origin, width, height, .. } => {
+        let x = p.0 - origin.0;
+        let y = p.1 - origin.1;
+
+        if x >= 0 && y >= 0 && (x as usize) < *width && (y as usize) < *height {
+          Some(y as usize * width + x as usize)
+        } else {
+          None
+        }
+      },
+      Backing::Sparse(_) => None,
+    }
+  }
+
+  pub fn contains(&self, p: Point) -> bool {
+    match &self.backing {
+      Backing::Dense { .. } => self.index(p).is_some(),
+      Backing::Sparse(cells) => cells.contains_key(&p),
+    }
+  }
+
+  pub fn get(&self, p: Point) -> &T {
+    let cell = match &self.backing {
+      Backing::Dense { cells, .. } => self.index(p).map(|i| &cells[i]),
+      Backing::Sparse(cells) => cells.get(&p),
+    };
+
+    cell.unwrap_or(&self.default)
+  }
+
+  // Panics if a dense grid does not contain `p`.
+  pub fn set(&mut self, p: Point, value: T) {
+    let index = self.index(p);
+
+    match &mut self.backing {
+      Backing::Dense { cells, .. } => match index {
+        Some(i) => cells[i] = value,
+        None => panic!("{:?} is outside of the grid", p),
+      },
+      Backing::Sparse(cells) => {
+        cells.insert(p, value);
+      },
+    }
+  }
+
+  // Makes room for `p` in a dense grid, at least doubling it towards `p`
+  // so that walking off the edge repeatedly stays cheap. Sparse grids
+  // have room everywhere.
+  pub fn grow(&mut self, p: Point) {
+    let (origin, width, height) = match &self.backing {
+      Backing::Dense { origin, width, height, .. } if self.index(p).is_none() => (*origin, *width, *height),
+      _ => return,
+    };
+
+    let (x, width) = extend(origin.0, width, p.0);
+    let (y, height) = extend(origin.1, height, p.1);
+
+    let mut grown = Grid::dense(width, height, self.default.clone()).with_origin((x, y));
+    grown.paste((0, 0), self);
+    *self = grown;
+  }
+
+  // Grows dense grids and stores the default in sparse ones if needed.
+  pub fn get_mut(&mut self, p: Point) -> &mut T {
+    let index = self.index(p).or_else(|| {
+      self.grow(p);
+      self.index(p)
+    });
+
+    match &mut self.backing {
+      Backing::Dense { cells, .. } => &mut cells[index.unwrap()],
+      Backing::Sparse(cells) => cells.entry(p).or_insert_with(|| self.default.clone()),
+    }
+  }
+
+  // Top left and bottom right corner, both inclusive.
+  pub fn bounds(&self) -> Option<(Point, Point)> {
+    match &self.backing {
+      Backing::Dense { width: 0, .. } | Backing::Dense { height: 0, .. } => None,
+      Backing::Dense { origin, width, height, .. } =>
+        Some((*origin, (origin.0 + *width as isize - 1, origin.1 + *height as isize - 1))),
+      Backing::Sparse(cells) => {
+        let xs = cells.keys().map(|p| p.0);
+        let ys = cells.keys().map(|p| p.1);
+
+        Some(((xs.clone().min()?, ys.clone().min()?), (xs.max()?, ys.max()?)))
+      },
+    }
+  }
+
+  pub fn width(&self) -> usize {
+    self.bounds().map(|(min, max)| (max.0 - min.0 + 1) as usize).unwrap_or(0)
+  }
+
+  pub fn height(&self) -> usize {
+    self.bounds().map(|(min, max)| (max.1 - min.1 + 1) as usize).unwrap_or(0)
+  }
+
+  // All stored cells, row by row for dense grids.
+  pub fn iter(&self) -> Box<dyn Iterator<Item = (Point, &T)> + '_> {
+    match &self.backing {
+      Backing::Dense { origin, width, cells, .. } => Box::new(cells.iter()
+        .enumerate()
+        .map(move |(i, v)| ((origin.0 + (i % width) as isize, origin.1 + (i / width) as isize), v))),
+      Backing::Sparse(cells) => Box::new(cells.iter().map(|(p, v)| (*p, v))),
+    }
+  }
+
+  // The stored neighbours of `p`.
+  pub fn neighbours(&self, p: Point, connectivity: Connectivity) -> impl Iterator<Item = (Point, &T)> + '_ {
+    connectivity.deltas().iter()
+      .map(move |d| (p.0 + d.0, p.1 + d.1))
+      .filter(move |n| self.contains(*n))
+      .map(move |n| (n, self.get(n)))
+  }
+
+  // Copies every cell to `f(x, y)`, both relative to the top left corner.
+  // The result has the same top left corner.
+  fn remap<F: Fn(isize, isize) -> Point>(&self, width: usize, height: usize, f: F) -> Self {
+    let min = self.bounds().map(|(min, _)| min).unwrap_or((0, 0));
+    let mut result = match self.backing {
+      Backing::Dense { .. } => Grid::dense(width, height, self.default.clone()).with_origin(min),
+      Backing::Sparse(_) => Grid::sparse(self.default.clone()),
+    };
+
+    for (p, v) in self.iter() {
+      let (x, y) = f(p.0 - min.0, p.1 - min.1);
+      result.set((min.0 + x, min.1 + y), v.clone());
+    }
+
+    result
+  }
+
+  pub fn transpose(&self) -> Self {
+    self.remap(self.height(), self.width(), |x, y| (y, x))
+  }
+
+  // Upside down.
+  pub fn flip(&self) -> Self {
+    let h = self.height() as isize;
+    self.remap(self.width(), self.height(), |x, y| (x, h - 1 - y))
+  }
+
+  // Left to right.
+  pub fn mirror(&self) -> Self {
+    let w = self.width() as isize;
+    self.remap(self.width(), self.height(), |x, y| (w - 1 - x, y))
+  }
+
+  // Clockwise by 90 degrees.
+  pub fn rotate(&self) -> Self {
+    self.transpose().mirror()
+  }
+
+  // The dense `width` x `height` part of the grid starting at `origin`,
+  // moved to (0, 0).
+  pub fn sub(&self, origin: Point, width: usize, height: usize) -> Self {
+    let mut result = Grid::dense(width, height, self.default.clone());
+
+    for y in 0..height as isize {
+      for x in 0..width as isize {
+        result.set((x, y), self.get((origin.0 + x, origin.1 + y)).clone());
+      }
+    }
+
+    result
+  }
+
+  // Copies all cells of `other` into this grid, shifted by `offset`.
+  pub fn paste(&mut self, offset: Point, other: &Grid<T>) {
+    for (p, v) in other.iter() {
+      self.set((p.0 + offset.0, p.1 + offset.1), v.clone());
+    }
+  }
+}
+
+// Start and length of a dense dimension that includes `p`.
+fn extend(start: isize, len: usize, p: isize) -> (isize, usize) {
+  let min = len.max(1) as isize;
+
+  if p < start {
+    let more = (start - p).max(min);
+    (start - more, len + more as usize)
+  } else if p >= start + len as isize {
+    let more = (p - start - len as isize + 1).max(min);
+    (start, len + more as usize)
+  } else {
+    (start, len)
+  }
+}
+
+// Row by row over the bounds, cells that are not stored are shown as
+// the default.
+impl<T: Clone + fmt::Display> fmt::Display for Grid<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let (min, max) = match self.bounds() {
+      Some(b) => b,
+      None => return Ok(()),
+    };
+
+    for y in min.1..=max.1 {
+      if y != min.1 {
+        writeln!(f)?;
+      }
+
+      for x in min.0..=max.0 {
+        write!(f, "{}", self.get((x, y)))?;
+      }
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn example() -> Grid<char> {
+    Grid::parse("ab\ncd\nef", ' ', Ok).unwrap()
+  }
+
+  #[test]
+  pub fn parse_and_display() {
+    let grid = Grid::parse("#.\n#", '.', Ok).unwrap();
+
+    assert_eq!(2, grid.width());
+    assert_eq!(2, grid.height());
+    assert_eq!('.', *grid.get((1, 1)));
+    assert_eq!('.', *grid.get((5, 5)));
+    assert!(!grid.contains((5, 5)));
+    assert_eq!("#.\n#.", grid.to_string());
+  }
+
+  #[test]
+  pub fn transformations() {
+    let grid = example();
+
+    assert_eq!("ace\nbdf", grid.transpose().to_string());
+    assert_eq!("ef\ncd\nab", grid.flip().to_string());
+    assert_eq!("ba\ndc\nfe", grid.mirror().to_string());
+    assert_eq!("eca\nfdb", grid.rotate().to_string());
+    assert_eq!(grid, grid.rotate().rotate().rotate().rotate());
+  }
+
+  #[test]
+  pub fn sparse_grids_behave_like_dense_ones() {
+    let grid = example().with_origin((-1, -1));
+    let sparse = grid.to_sparse();
+
+    assert!(!sparse.is_dense());
+    assert_eq!(grid.bounds(), sparse.bounds());
+    assert_eq!(Some(((-1, -1), (0, 1))), sparse.bounds());
+    assert_eq!(grid.rotate().to_string(), sparse.rotate().to_string());
+    assert_eq!('d', *sparse.get((0, 0)));
+  }
+
+  #[test]
+  pub fn neighbours() {
+    let grid = example();
+
+    let four = grid.neighbours((0, 0), Connectivity::Four).map(|(_, v)| *v).collect::<String>();
+    let eight = grid.neighbours((0, 1), Connectivity::Eight).map(|(_, v)| *v).collect::<String>();
+
+    assert_eq!("bc", four);
+    assert_eq!("abdef", eight);
+  }
+
+  #[test]
+  pub fn grow_keeps_cells() {
+    let mut grid = example();
+
+    *grid.get_mut((-1, 4)) = 'x';
+
+    assert!(grid.is_dense());
+    assert_eq!(Some(((-2, 0), (1, 5))), grid.bounds());
+    assert_eq!("  ab\n  cd\n  ef\n    \n x  \n    ", grid.to_string());
+
+    let mut empty = Grid::dense(0, 0, '.');
+    empty.grow((3, 3));
+    assert!(empty.contains((3, 3)));
+  }
+
+  #[test]
+  pub fn sub_and_paste() {
+    let grid = example();
+    let mut empty = Grid::sparse(' ');
+
+    empty.paste((10, 10), &grid.sub((0, 1), 2, 2));
+
+    assert_eq!("cd\nef", empty.to_string());
+    assert_eq!(Some(((10, 10), (11, 11))), empty.bounds());
+  }
+}
@@ -1,6 +1,9 @@
 use std::num::{ParseIntError, ParseFloatError};
 use std::fmt;
 
+pub mod big;
+pub mod grid;
+
 #[derive(Debug)]
 pub struct Error {
   pub what: String
@@ -64,4 +67,56 @@ impl From<ParseError> for Error {
   fn from(error: ParseError) -> Self {
     Error::new(&format!("Unable to parse something: {}", error))
   }
-}
\ No newline at end of file
+}
+
+// Limits the number of steps an interpreter may execute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+  pub limit: Option<usize>,
+  pub used: usize,
+}
+
+impl Budget {
+  pub fn new(limit: usize) -> Budget {
+    Budget { limit: Some(limit), used: 0 }
+  }
+
+  pub fn unlimited() -> Budget {
+    Budget { limit: None, used: 0 }
+  }
+
+  // Accounts for one more step. If the budget is exhausted the state of
+  // the interpreter is captured in the error.
+  pub fn spend<S, F>(&mut self, state: F) -> Result<(), Exhausted<S>>
+    where F: FnOnce() -> S {
+
+    if let Some(limit) = self.limit {
+      if self.used >= limit {
+        return Err(Exhausted { steps: self.used, state: state() });
+      }
+    }
+
+    self.used += 1;
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exhausted<S> {
+  pub steps: usize,
+  pub state: S,
+}
+
+impl<S> fmt::Display for Exhausted<S> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Step budget exhausted after {} steps", self.steps)
+  }
+}
+
+impl<S: fmt::Debug> std::error::Error for Exhausted<S> {}
+
+impl<S> From<Exhausted<S>> for ParseError {
+  fn from(error: Exhausted<S>) -> Self {
+    ParseError::new(&error.to_string())
+  }
+}
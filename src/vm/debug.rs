@@ -0,0 +1,274 @@
+use std::fmt::Write;
+use super::{Instruction, Io, Machine, State};
+
+// A single executed instruction together with the registers it changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub step: usize,
+    pub ip: isize,
+    pub instruction: Instruction,
+    pub changes: Vec<(char, isize, isize)>,
+}
+
+pub enum Breakpoint {
+    Ip(isize),
+    Register(char, Box<dyn Fn(isize) -> bool>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    // The machine hit the breakpoint with the given index.
+    Breakpoint(usize),
+    // The machine halted or blocked on input.
+    Machine(State),
+}
+
+pub struct Debugger<I: Io> {
+    pub machine: Machine<I>,
+    pub trace: Vec<Event>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl<I: Io> Debugger<I> {
+    pub fn new(machine: Machine<I>) -> Self {
+        let trace = vec![];
+        let breakpoints = vec![];
+
+        Debugger { machine, trace, breakpoints }
+    }
+
+    pub fn break_at(&mut self, ip: isize) -> usize {
+        self.breakpoints.push(Breakpoint::Ip(ip));
+        self.breakpoints.len() - 1
+    }
+
+    pub fn break_when<F>(&mut self, register: char, predicate: F) -> usize
+        where F: Fn(isize) -> bool + 'static {
+
+        self.breakpoints.push(Breakpoint::Register(register, Box::new(predicate)));
+        self.breakpoints.len() - 1
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    // Executes a single instruction and records it in the trace.
+    pub fn step(&mut self) -> State {
+        // a machine stopped by rcv still points at it
+        if self.machine.state() == State::Halted {
+            return State::Halted;
+        }
+
+        let ip = self.machine.ip;
+        let instruction = match self.machine.current() {
+            Some(i) => i.clone(),
+            None => return self.machine.state(),
+        };
+
+        let targets = instruction.targets();
+        let before = targets.iter().map(|r| self.machine.get(*r)).collect::<Vec<_>>();
+
+        let state = self.machine.step();
+        if state == State::Blocked {
+            return state;
+        }
+
+        let changes = targets.into_iter()
+            .zip(before)
+            .map(|(r, b)| (r, b, self.machine.get(r)))
+            .filter(|(_, b, a)| b != a)
+            .collect::<Vec<_>>();
+
+        let step = self.trace.len();
+        self.trace.push(Event { step, ip, instruction, changes });
+
+        state
+    }
+
+    fn matches(&self) -> Vec<bool> {
+        self.breakpoints.iter()
+            .map(|b| match b {
+                Breakpoint::Ip(ip) => *ip == self.machine.ip,
+                Breakpoint::Register(r, predicate) => predicate(self.machine.get(*r)),
+            })
+            .collect()
+    }
+
+    // Runs until a breakpoint is hit or the machine stops. Breakpoints
+    // are checked after every instruction and only hit when they did not
+    // match before it, so calling `run` again continues past the
+    // breakpoint that was just hit. A register breakpoint fires again once
+    // its predicate turned false and then true again.
+    pub fn run(&mut self) -> Stop {
+        loop {
+            let before = self.matches();
+            let state = self.step();
+            if state != State::Running {
+                return Stop::Machine(state);
+            }
+
+            let hit = self.matches().into_iter()
+                .zip(before)
+                .position(|(now, before)| now && !before);
+
+            if let Some(b) = hit {
+                return Stop::Breakpoint(b);
+            }
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        for e in &self.trace {
+            let _ = write!(out, "{:>6} {:>4}: {:<12}", e.step, e.ip, e.instruction.to_string());
+            for (r, before, after) in &e.changes {
+                let _ = write!(out, " {}: {} -> {}", r, before, after);
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let events = self.trace.iter()
+            .map(|e| {
+                let changes = e.changes.iter()
+                    .map(|(r, before, after)|
+                        format!("{{\"register\":\"{}\",\"before\":{},\"after\":{}}}", escape(&r.to_string()), before, after))
+                    .collect::<Vec<_>>();
+
+                format!("{{\"step\":{},\"ip\":{},\"instruction\":\"{}\",\"changes\":[{}]}}",
+                    e.step, e.ip, escape(&e.instruction.to_string()), changes.join(","))
+            })
+            .collect::<Vec<_>>();
+
+        format!("[{}]", events.join(","))
+    }
+}
+
+// The contents of a JSON string with the characters it cannot hold as
+// they are escaped.
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            },
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vm::{parse, Receive};
+
+    fn debugger(program: &str) -> Debugger<()> {
+        let program = parse(program).unwrap();
+        Debugger::new(Machine::new(&program, ()))
+    }
+
+    #[test]
+    pub fn trace_records_register_changes() {
+        let mut d = debugger("set a 3\nsub a 1\njnz a -1\nmul a 2");
+
+        assert_eq!(Stop::Machine(State::Halted), d.run());
+        assert_eq!(8, d.trace.len());
+        assert_eq!(vec![('a', 0, 3)], d.trace[0].changes);
+        assert!(d.trace[2].changes.is_empty());
+        assert!(d.trace[7].changes.is_empty());
+    }
+
+    #[test]
+    pub fn break_at_ip() {
+        let mut d = debugger("set a 3\nsub a 1\njnz a -1\nset b 1");
+        let b = d.break_at(3);
+
+        assert_eq!(Stop::Breakpoint(b), d.run());
+        assert_eq!(0, d.machine.get('a'));
+        assert_eq!(0, d.machine.get('b'));
+
+        assert_eq!(Stop::Machine(State::Halted), d.run());
+        assert_eq!(1, d.machine.get('b'));
+    }
+
+    #[test]
+    pub fn break_when_register_matches() {
+        let mut d = debugger("set a 3\nsub a 1\njnz a -1");
+        let b = d.break_when('a', |v| v == 1);
+
+        assert_eq!(Stop::Breakpoint(b), d.run());
+        assert_eq!(1, d.machine.get('a'));
+    }
+
+    #[test]
+    pub fn register_breakpoints_fire_on_change() {
+        // a stays 1 for two instructions, then becomes 0 and 1 again
+        let mut d = debugger("set a 1\nset b 1\nset a 0\nset a 1\nset b 2");
+        let b = d.break_when('a', |v| v == 1);
+
+        assert_eq!(Stop::Breakpoint(b), d.run());
+        assert_eq!(1, d.trace.len());
+        assert_eq!(Stop::Breakpoint(b), d.run());
+        assert_eq!(4, d.trace.len());
+        assert_eq!(Stop::Machine(State::Halted), d.run());
+        assert_eq!(2, d.machine.get('b'));
+    }
+
+    struct Stopper;
+
+    impl Io for Stopper {
+        fn snd(&mut self, _value: isize) -> bool {
+            true
+        }
+
+        fn rcv(&mut self, _current: isize) -> Receive {
+            Receive::Stop
+        }
+    }
+
+    #[test]
+    pub fn nothing_runs_after_rcv_stops() {
+        let program = parse("set a 1\nrcv a\nset a 2").unwrap();
+        let mut d = Debugger::new(Machine::new(&program, Stopper));
+
+        assert_eq!(Stop::Machine(State::Halted), d.run());
+        assert_eq!(2, d.trace.len());
+        assert_eq!(State::Halted, d.step());
+        assert_eq!(Stop::Machine(State::Halted), d.run());
+        assert_eq!(2, d.trace.len());
+        assert_eq!(1, d.machine.get('a'));
+    }
+
+    #[test]
+    pub fn dumps() {
+        let mut d = debugger("set a 3\njnz a 2");
+        d.run();
+
+        assert_eq!("     0    0: set a 3      a: 0 -> 3\n     1    1: jnz a 2     \n", d.to_text());
+        assert_eq!(
+            "[{\"step\":0,\"ip\":0,\"instruction\":\"set a 3\",\"changes\":[{\"register\":\"a\",\"before\":0,\"after\":3}]},\
+             {\"step\":1,\"ip\":1,\"instruction\":\"jnz a 2\",\"changes\":[]}]",
+            d.to_json());
+    }
+
+    #[test]
+    pub fn json_escapes_registers() {
+        let mut d = debugger("set \" 1\nset \\ 2");
+        d.run();
+
+        assert_eq!(
+            "[{\"step\":0,\"ip\":0,\"instruction\":\"set \\\" 1\",\"changes\":[{\"register\":\"\\\"\",\"before\":0,\"after\":1}]},\
+             {\"step\":1,\"ip\":1,\"instruction\":\"set \\\\ 2\",\"changes\":[{\"register\":\"\\\\\",\"before\":0,\"after\":2}]}]",
+            d.to_json());
+    }
+}
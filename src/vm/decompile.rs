@@ -0,0 +1,369 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use super::{Instruction, Param};
+
+// Splits a program into basic blocks. A block starts at the beginning of
+// the program, at every jump target and after every jump.
+pub fn basic_blocks(program: &[Instruction]) -> Vec<Range<usize>> {
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+    leaders.insert(program.len());
+
+    for (i, instr) in program.iter().enumerate() {
+        if let Some(jump) = Jump::new(i, instr) {
+            leaders.insert(i + 1);
+            if let Some(t) = jump.target {
+                if t >= 0 && (t as usize) <= program.len() {
+                    leaders.insert(t as usize);
+                }
+            }
+        }
+    }
+
+    let leaders = leaders.into_iter().collect::<Vec<_>>();
+    leaders.windows(2)
+        .map(|w| w[0]..w[1])
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct Condition {
+    param: Param,
+    op: &'static str,
+}
+
+impl Condition {
+    fn negate(&self) -> Self {
+        let op = match self.op {
+            "!=" => "==",
+            "==" => "!=",
+            ">" => "<=",
+            _ => ">",
+        };
+
+        Condition { param: self.param.clone(), op }
+    }
+
+    fn always(&self) -> bool {
+        match (&self.param, self.op) {
+            (Param::Value(v), "!=") => *v != 0,
+            (Param::Value(v), ">") => *v > 0,
+            _ => false,
+        }
+    }
+}
+
+struct Jump {
+    condition: Condition,
+    target: Option<isize>,
+    offset: Param,
+}
+
+impl Jump {
+    fn new(i: usize, instr: &Instruction) -> Option<Self> {
+        use Instruction::*;
+
+        let (param, offset, op) = match instr {
+            Jnz((a, b)) => (a, b, "!="),
+            Jgz((a, b)) => (a, b, ">"),
+            _ => return None,
+        };
+
+        let target = match offset {
+            Param::Value(v) => Some(i as isize + v),
+            Param::Register(_) => None,
+        };
+
+        let condition = Condition { param: param.clone(), op };
+        Some(Jump { condition, target, offset: offset.clone() })
+    }
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Simple(usize, String),
+    Goto(usize, Option<Condition>, String),
+    Break(usize),
+    If(usize, Condition, Vec<Stmt>, Vec<Stmt>),
+    Loop(usize, Option<Condition>, Vec<Stmt>),
+}
+
+struct Decompiler<'a> {
+    program: &'a [Instruction],
+    names: &'a HashMap<char, String>,
+    labels: BTreeSet<usize>,
+}
+
+impl<'a> Decompiler<'a> {
+    fn name(&self, r: char) -> String {
+        self.names.get(&r).cloned().unwrap_or_else(|| r.to_string())
+    }
+
+    fn param(&self, p: &Param) -> String {
+        match p {
+            Param::Register(r) => self.name(*r),
+            Param::Value(v) => v.to_string(),
+        }
+    }
+
+    fn condition(&self, c: &Condition) -> String {
+        format!("{} {} 0", self.param(&c.param), c.op)
+    }
+
+    fn statement(&self, instr: &Instruction) -> String {
+        use Instruction::*;
+
+        match instr {
+            Snd(p) => format!("snd({})", self.param(p)),
+            Set((a, p)) => format!("{} = {}", self.name(*a), self.param(p)),
+            Add((a, Param::Value(v))) if *v < 0 => format!("{} -= {}", self.name(*a), -v),
+            Add((a, p)) => format!("{} += {}", self.name(*a), self.param(p)),
+            Sub((a, Param::Value(v))) if *v < 0 => format!("{} += {}", self.name(*a), -v),
+            Sub((a, p)) => format!("{} -= {}", self.name(*a), self.param(p)),
+            Mul((a, p)) => format!("{} *= {}", self.name(*a), self.param(p)),
+            Mod((a, p)) => format!("{} %= {}", self.name(*a), self.param(p)),
+            Rcv(a) => format!("{} = rcv({})", self.name(*a), self.name(*a)),
+            Jgz(_) | Jnz(_) => instr.to_string(),
+            Fused(fused) => {
+                let args = fused.targets().into_iter().map(|r| self.name(r)).collect::<Vec<_>>();
+                format!("{} // {}", fused, args.join(", "))
+            },
+        }
+    }
+
+    fn jump(&mut self, i: usize, condition: Option<Condition>, jump: &Jump, exit: Option<usize>) -> Stmt {
+        match jump.target {
+            Some(t) if Some(t as usize) == exit && t >= 0 => match condition {
+                Some(c) => Stmt::If(i, c, vec![Stmt::Break(i)], vec![]),
+                None => Stmt::Break(i),
+            },
+            Some(t) => {
+                if t >= 0 {
+                    self.labels.insert(t as usize);
+                }
+                Stmt::Goto(i, condition, format!("L{}", t))
+            },
+            None => Stmt::Goto(i, condition, format!("ip + {}", self.param(&jump.offset))),
+        }
+    }
+
+    // Turns the instructions in `lo..hi` into structured statements.
+    // `exit` is the instruction right after the innermost loop.
+    fn structure(&mut self, lo: usize, hi: usize, exit: Option<usize>) -> Vec<Stmt> {
+        let mut result = vec![];
+        let mut i = lo;
+
+        while i < hi {
+            // a jump from further down back to this instruction closes a loop
+            let back = (i..hi).rev()
+                .filter_map(|j| Jump::new(j, &self.program[j]).map(|jump| (j, jump)))
+                .find(|(_, jump)| jump.target == Some(i as isize));
+
+            if let Some((j, jump)) = back {
+                let body = self.structure(i, j, Some(j + 1));
+                let condition = Some(jump.condition).filter(|c| !c.always());
+                result.push(Stmt::Loop(i, condition, body));
+                i = j + 1;
+                continue;
+            }
+
+            let jump = match Jump::new(i, &self.program[i]) {
+                Some(jump) => jump,
+                None => {
+                    result.push(Stmt::Simple(i, self.statement(&self.program[i])));
+                    i += 1;
+                    continue;
+                },
+            };
+
+            if jump.condition.always() {
+                let stmt = self.jump(i, None, &jump, exit);
+                result.push(stmt);
+                i += 1;
+                continue;
+            }
+
+            let t = jump.target.unwrap_or(-1);
+            if t > i as isize + 1 && t as usize <= hi {
+                let start = i;
+                let t = t as usize;
+
+                // the then branch may end with a jump over an else branch
+                let otherwise = Jump::new(t - 1, &self.program[t - 1])
+                    .filter(|j| j.condition.always())
+                    .and_then(|j| j.target)
+                    .filter(|t2| *t2 > t as isize && *t2 as usize <= hi)
+                    .map(|t2| t2 as usize);
+
+                let stmt = match otherwise {
+                    Some(t2) => {
+                        let then = self.structure(i + 1, t - 1, exit);
+                        let otherwise = self.structure(t, t2, exit);
+                        i = t2;
+
+                        if then.is_empty() {
+                            Stmt::If(start, jump.condition, otherwise, vec![])
+                        } else {
+                            Stmt::If(start, jump.condition.negate(), then, otherwise)
+                        }
+                    },
+                    None => {
+                        let then = self.structure(i + 1, t, exit);
+                        i = t;
+                        Stmt::If(start, jump.condition.negate(), then, vec![])
+                    },
+                };
+
+                result.push(stmt);
+                continue;
+            }
+
+            let stmt = self.jump(i, Some(jump.condition.clone()), &jump, exit);
+            result.push(stmt);
+            i += 1;
+        }
+
+        result
+    }
+
+    fn render(&self, stmts: &[Stmt], depth: usize, out: &mut String) {
+        let indent = "    ".repeat(depth);
+
+        for stmt in stmts {
+            let start = match stmt {
+                Stmt::Simple(i, _) | Stmt::Goto(i, _, _) | Stmt::Break(i)
+                | Stmt::If(i, _, _, _) | Stmt::Loop(i, _, _) => *i,
+            };
+            if self.labels.contains(&start) {
+                out.push_str(&format!("{}L{}:\n", indent, start));
+            }
+
+            match stmt {
+                Stmt::Simple(_, s) => out.push_str(&format!("{}{}\n", indent, s)),
+                Stmt::Break(_) => out.push_str(&format!("{}break\n", indent)),
+                Stmt::Goto(_, None, target) => out.push_str(&format!("{}goto {}\n", indent, target)),
+                Stmt::Goto(_, Some(c), target) =>
+                    out.push_str(&format!("{}if {} {{ goto {} }}\n", indent, self.condition(c), target)),
+                Stmt::If(_, c, then, otherwise) => {
+                    out.push_str(&format!("{}if {} {{\n", indent, self.condition(c)));
+                    self.render(then, depth + 1, out);
+                    if !otherwise.is_empty() {
+                        out.push_str(&format!("{}}} else {{\n", indent));
+                        self.render(otherwise, depth + 1, out);
+                    }
+                    out.push_str(&format!("{}}}\n", indent));
+                },
+                Stmt::Loop(_, None, body) => {
+                    out.push_str(&format!("{}loop {{\n", indent));
+                    self.render(body, depth + 1, out);
+                    out.push_str(&format!("{}}}\n", indent));
+                },
+                Stmt::Loop(_, Some(c), body) => {
+                    out.push_str(&format!("{}do {{\n", indent));
+                    self.render(body, depth + 1, out);
+                    out.push_str(&format!("{}}} while {}\n", indent, self.condition(c)));
+                },
+            }
+        }
+    }
+}
+
+// Turns a program into structured pseudo-code. Registers can be given
+// more meaningful names through `names`.
+pub fn decompile(program: &[Instruction], names: &HashMap<char, String>) -> String {
+    let labels = BTreeSet::new();
+    let mut decompiler = Decompiler { program, names, labels };
+
+    let stmts = decompiler.structure(0, program.len(), None);
+
+    let mut out = String::new();
+    decompiler.render(&stmts, 0, &mut out);
+
+    if decompiler.labels.contains(&program.len()) {
+        out.push_str(&format!("L{}:\n", program.len()));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vm::parse;
+
+    #[test]
+    pub fn basic_blocks_split_at_jumps() {
+        let program = parse("set a 3\nsub a 1\njnz a -1\nset b 1").unwrap();
+
+        assert_eq!(vec![0..1, 1..3, 3..4], basic_blocks(&program));
+    }
+
+    #[test]
+    pub fn decompile_day23() {
+        let program = parse(include_str!("../day23/input")).unwrap();
+        let mut names = HashMap::new();
+        names.insert('h', "nonprimes".to_string());
+
+        let expected = "\
+b = 65
+c = b
+if a != 0 {
+    b *= 100
+    b += 100000
+    c = b
+    c += 17000
+}
+loop {
+    f = 1
+    d = 2
+    do {
+        e = 2
+        do {
+            g = d
+            g *= e
+            g -= b
+            if g == 0 {
+                f = 0
+            }
+            e += 1
+            g = e
+            g -= b
+        } while g != 0
+        d += 1
+        g = d
+        g -= b
+    } while g != 0
+    if f == 0 {
+        nonprimes += 1
+    }
+    g = b
+    g -= c
+    if g == 0 {
+        break
+    }
+    b += 17
+}
+";
+
+        assert_eq!(expected, decompile(&program, &names));
+    }
+
+    #[test]
+    pub fn decompile_unstructured_jumps() {
+        let program = parse("add a 1\njgz a 3\njnz a -2\nsnd a\nrcv b\njgz b a").unwrap();
+
+        let expected = "\
+do {
+    a += 1
+    if a > 0 { goto L4 }
+} while a != 0
+snd(a)
+L4:
+b = rcv(b)
+if b > 0 { goto ip + a }
+";
+
+        assert_eq!(expected, decompile(&program, &HashMap::new()));
+    }
+}
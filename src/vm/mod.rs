@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Error};
+use std::str::FromStr;
+use crate::utils::{Budget, Exhausted, ParseError};
+
+pub mod debug;
+pub mod decompile;
+pub mod optimize;
+pub mod scheduler;
+
+use optimize::Fused;
+
+fn first(s: &str) -> Result<char, ParseError> {
+    s.chars().next().ok_or(ParseError::new("Cannot parse empty string"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Param {
+    Register(char),
+    Value(isize),
+}
+
+impl FromStr for Param {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        use Param::*;
+
+        let first = first(s)?;
+        if s.len() == 1 && first.is_alphabetic() {
+            return Ok(Register(first));
+        }
+
+        Ok(Value(s.parse::<isize>()?))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    Snd(Param),
+    Set((char, Param)),
+    Add((char, Param)),
+    Sub((char, Param)),
+    Mul((char, Param)),
+    Mod((char, Param)),
+    Rcv(char),
+    Jgz((Param, Param)),
+    Jnz((Param, Param)),
+    // Super-instructions produced by the optimizer.
+    Fused(Box<Fused>),
+}
+
+impl FromStr for Instruction {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        use Instruction::*;
+
+        let i = s.split(' ').collect::<Vec<_>>();
+
+        let arity = match i[0] {
+            "snd" | "rcv" => 2,
+            _ => 3,
+        };
+
+        if i.len() != arity {
+            return Err(ParseError::new("Invalid input"));
+        }
+
+        Ok(match i[0] {
+            "snd" => Snd(Param::from_str(i[1])?),
+            "set" => Set((first(i[1])?, Param::from_str(i[2])?)),
+            "add" => Add((first(i[1])?, Param::from_str(i[2])?)),
+            "sub" => Sub((first(i[1])?, Param::from_str(i[2])?)),
+            "mul" => Mul((first(i[1])?, Param::from_str(i[2])?)),
+            "mod" => Mod((first(i[1])?, Param::from_str(i[2])?)),
+            "rcv" => Rcv(first(i[1])?),
+            "jgz" => Jgz((Param::from_str(i[1])?, Param::from_str(i[2])?)),
+            "jnz" => Jnz((Param::from_str(i[1])?, Param::from_str(i[2])?)),
+            _ => return Err(ParseError::new("Invalid instruction")),
+        })
+    }
+}
+
+impl Display for Param {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            Param::Register(r) => write!(f, "{}", r),
+            Param::Value(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        use Instruction::*;
+
+        match self {
+            Snd(p) => write!(f, "snd {}", p),
+            Set((a, p)) => write!(f, "set {} {}", a, p),
+            Add((a, p)) => write!(f, "add {} {}", a, p),
+            Sub((a, p)) => write!(f, "sub {} {}", a, p),
+            Mul((a, p)) => write!(f, "mul {} {}", a, p),
+            Mod((a, p)) => write!(f, "mod {} {}", a, p),
+            Rcv(a) => write!(f, "rcv {}", a),
+            Jgz((a, b)) => write!(f, "jgz {} {}", a, b),
+            Jnz((a, b)) => write!(f, "jnz {} {}", a, b),
+            Fused(fused) => write!(f, "{}", fused),
+        }
+    }
+}
+
+impl Instruction {
+    // The registers an instruction may write to.
+    pub fn targets(&self) -> Vec<char> {
+        use Instruction::*;
+
+        match self {
+            Set((a, _)) | Add((a, _)) | Sub((a, _)) | Mul((a, _)) | Mod((a, _)) | Rcv(a) => vec![*a],
+            Snd(_) | Jgz(_) | Jnz(_) => vec![],
+            Fused(fused) => fused.targets(),
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Vec<Instruction>, ParseError> {
+    input
+        .lines()
+        .map(Instruction::from_str)
+        .collect::<Result<Vec<_>, ParseError>>()
+}
+
+// What a machine should do when it executes a `rcv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Receive {
+    // Store the value in the register and continue.
+    Value(isize),
+    // Leave the register untouched and continue.
+    Skip,
+    // Stay on the `rcv` until new input is available.
+    Block,
+    // Stop the machine for good.
+    Stop,
+}
+
+// The I/O semantics of `snd` and `rcv` differ between the puzzles,
+// everything else is shared. `snd` returns false if the value cannot
+// be sent yet, the machine then blocks on the `snd`.
+pub trait Io {
+    fn snd(&mut self, value: isize) -> bool;
+    fn rcv(&mut self, current: isize) -> Receive;
+}
+
+// Machines without any I/O, e.g. the coprocessor from day 23.
+impl Io for () {
+    fn snd(&mut self, _value: isize) -> bool {
+        true
+    }
+
+    fn rcv(&mut self, _current: isize) -> Receive {
+        Receive::Skip
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Running,
+    Blocked,
+    Halted,
+}
+
+// The state of a machine, e.g. when it ran out of budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub ip: isize,
+    pub registers: HashMap<char, isize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Machine<I: Io> {
+    instructions: Vec<Instruction>,
+    pub registers: HashMap<char, isize>,
+    pub ip: isize,
+    pub io: I,
+    pub muls: usize,
+    pub steps: usize,
+    halted: bool,
+}
+
+impl<I: Io> Machine<I> {
+    pub fn new(instructions: &[Instruction], io: I) -> Self {
+        let instructions = instructions.to_vec();
+        let registers = HashMap::new();
+        let ip = 0;
+        let muls = 0;
+        let steps = 0;
+        let halted = false;
+
+        Machine { instructions, registers, ip, io, muls, steps, halted }
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn current(&self) -> Option<&Instruction> {
+        if self.ip < 0 {
+            return None;
+        }
+
+        self.instructions.get(self.ip as usize)
+    }
+
+    pub fn get(&self, r: char) -> isize {
+        *self.registers.get(&r).unwrap_or(&0)
+    }
+
+    fn resolve(&self, p: &Param) -> isize {
+        use Param::*;
+
+        match p {
+            Register(r) => self.get(*r),
+            Value(v) => *v,
+        }
+    }
+
+    fn update<F>(&mut self, a: char, p: &Param, f: F)
+        where F: Fn(isize, isize) -> isize {
+
+        let v = self.resolve(p);
+        let r = self.registers.entry(a).or_insert(0);
+        *r = f(*r, v);
+    }
+
+    fn jump(&self, condition: bool, offset: &Param) -> isize {
+        if condition {
+            self.resolve(offset)
+        } else {
+            1
+        }
+    }
+
+    pub fn state(&self) -> State {
+        if self.halted || self.ip < 0 || self.ip as usize >= self.instructions.len() {
+            State::Halted
+        } else {
+            State::Running
+        }
+    }
+
+    // Executes a single instruction. A blocked machine does not move
+    // its instruction pointer and can be stepped again once input is
+    // available.
+    pub fn step(&mut self) -> State {
+        if self.state() == State::Halted {
+            return State::Halted;
+        }
+
+        let instr = self.instructions[self.ip as usize].clone();
+
+        match self.execute(&instr) {
+            Ok(offset) => {
+                self.ip += offset;
+                self.steps += 1;
+                self.state()
+            },
+            Err(state) => state,
+        }
+    }
+
+    // Executes an instruction and returns the offset to the next one, or
+    // the state if the machine cannot continue.
+    fn execute(&mut self, instr: &Instruction) -> Result<isize, State> {
+        use Instruction::*;
+
+        let mut offset = 1;
+
+        match instr {
+            Snd(p) => {
+                let v = self.resolve(p);
+                if !self.io.snd(v) {
+                    return Err(State::Blocked);
+                }
+            },
+            Set((a, p)) => self.update(*a, p, |_, v| v),
+            Add((a, p)) => self.update(*a, p, |r, v| r + v),
+            Sub((a, p)) => self.update(*a, p, |r, v| r - v),
+            Mul((a, p)) => {
+                self.muls += 1;
+                self.update(*a, p, |r, v| r * v);
+            },
+            Mod((a, p)) => self.update(*a, p, |r, v| r % v),
+            Rcv(a) => match self.io.rcv(self.get(*a)) {
+                Receive::Value(v) => { self.registers.insert(*a, v); },
+                Receive::Skip => {},
+                Receive::Block => return Err(State::Blocked),
+                Receive::Stop => {
+                    self.halted = true;
+                    return Err(State::Halted);
+                },
+            },
+            Jgz((a, b)) => offset = self.jump(self.resolve(a) > 0, b),
+            Jnz((a, b)) => offset = self.jump(self.resolve(a) != 0, b),
+            Fused(fused) => {
+                if fused.apply(self) {
+                    offset = fused.length;
+                } else {
+                    return self.execute(&fused.original);
+                }
+            },
+        }
+
+        Ok(offset)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot { ip: self.ip, registers: self.registers.clone() }
+    }
+
+    // Runs the machine until it either halts or blocks on input.
+    pub fn run(&mut self) -> State {
+        loop {
+            let state = self.step();
+            if state != State::Running {
+                return state;
+            }
+        }
+    }
+
+    // Like `run`, but gives up once the budget is used up.
+    pub fn run_within(&mut self, budget: &mut Budget) -> Result<State, Exhausted<Snapshot>> {
+        if self.state() == State::Halted {
+            return Ok(State::Halted);
+        }
+
+        loop {
+            budget.spend(|| self.snapshot())?;
+
+            let state = self.step();
+            if state != State::Running {
+                return Ok(state);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn parse_accepts_all_instructions() {
+        let program = parse("snd 5\nset a 1\nadd a b\nsub a -2\nmul a a\nmod a 5\nrcv a\njgz a -1\njnz 1 3");
+
+        assert_eq!(9, program.unwrap().len());
+    }
+
+    #[test]
+    pub fn display_roundtrips() {
+        let input = "snd 5\nset a 1\nadd a b\nsub a -2\nmul a a\nmod a 5\nrcv a\njgz a -1\njnz 1 3";
+        let program = parse(input).unwrap();
+
+        let output = program.iter().map(|i| i.to_string()).collect::<Vec<_>>().join("\n");
+
+        assert_eq!(input, output);
+    }
+
+    #[test]
+    pub fn parse_rejects_unknown_instructions() {
+        assert!(parse("nop a 1").is_err());
+        assert!(parse("set a").is_err());
+    }
+
+    #[test]
+    pub fn run_without_io() {
+        let program = parse("set a 2\nmul a 3\nsub a 1\nmod a 3\nsub b 4").unwrap();
+        let mut machine = Machine::new(&program, ());
+
+        assert_eq!(State::Halted, machine.run());
+        assert_eq!(2, machine.get('a'));
+        assert_eq!(-4, machine.get('b'));
+        assert_eq!(1, machine.muls);
+        assert_eq!(5, machine.steps);
+    }
+
+    #[test]
+    pub fn run_within_budget() {
+        let program = parse("add a 1\njnz 1 -1").unwrap();
+        let mut machine = Machine::new(&program, ());
+
+        let exhausted = machine.run_within(&mut Budget::new(5)).unwrap_err();
+
+        assert_eq!(5, exhausted.steps);
+        assert_eq!(1, exhausted.state.ip);
+        assert_eq!(Some(&3), exhausted.state.registers.get(&'a'));
+    }
+
+    #[test]
+    pub fn jnz_and_jgz_loop() {
+        let program = parse("set a 3\nadd b 2\nsub a 1\njgz a -2\nset c 1\njnz c 2\nset c 5").unwrap();
+        let mut machine = Machine::new(&program, ());
+
+        machine.run();
+
+        assert_eq!(6, machine.get('b'));
+        assert_eq!(1, machine.get('c'));
+    }
+}
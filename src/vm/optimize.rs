@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Error};
+use super::{parse, Instruction, Io, Machine, Param};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    // target += factor * counter; counter = 0
+    MulAdd { target: char, factor: char, counter: char },
+    // flag = 0 if n is composite; outer = inner = n; scratch = 0
+    Composite { n: char, flag: char, outer: char, inner: char, scratch: char },
+}
+
+// A super-instruction replacing a whole loop. If its preconditions
+// do not hold at runtime the machine executes `original` instead and
+// walks through the loop the slow way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fused {
+    pub op: Op,
+    pub length: isize,
+    pub original: Instruction,
+}
+
+impl Display for Fused {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self.op {
+            Op::MulAdd { target, factor, counter } =>
+                write!(f, "muladd {} {} {}", target, factor, counter),
+            Op::Composite { n, flag, outer, inner, scratch } =>
+                write!(f, "composite {} {} {} {} {}", n, flag, outer, inner, scratch),
+        }
+    }
+}
+
+fn is_composite(n: isize) -> bool {
+    (2..).take_while(|d| d * d <= n).any(|d| n % d == 0)
+}
+
+impl Fused {
+    pub fn targets(&self) -> Vec<char> {
+        match self.op {
+            Op::MulAdd { target, counter, .. } => vec![target, counter],
+            Op::Composite { flag, outer, inner, scratch, .. } => vec![flag, outer, inner, scratch],
+        }
+    }
+
+    // Applies the fused operation to the machine. Returns false if the
+    // preconditions of the loop are not met.
+    pub fn apply<I: Io>(&self, machine: &mut Machine<I>) -> bool {
+        match self.op {
+            Op::MulAdd { target, factor, counter } => {
+                let c = machine.get(counter);
+                if c <= 0 {
+                    return false;
+                }
+
+                let v = machine.get(target) + machine.get(factor) * c;
+                machine.registers.insert(target, v);
+                machine.registers.insert(counter, 0);
+            },
+            Op::Composite { n, flag, outer, inner, scratch } => {
+                let v = machine.get(n);
+                if v < 3 {
+                    return false;
+                }
+
+                if is_composite(v) {
+                    machine.registers.insert(flag, 0);
+                }
+                machine.registers.insert(outer, v);
+                machine.registers.insert(inner, v);
+                machine.registers.insert(scratch, 0);
+                machine.muls += ((v - 2) * (v - 2)) as usize;
+            },
+        }
+
+        true
+    }
+}
+
+// Idioms are written in assembly with upper case letters as placeholders
+// for registers.
+const IDIOMS: [&str; 5] = [
+    "add A B\nsub C 1\njnz C -2",
+    "add A B\nadd C -1\njnz C -2",
+    "sub C 1\nadd A B\njnz C -2",
+    "add C -1\nadd A B\njnz C -2",
+    "set D 2\nset E 2\nset G D\nmul G E\nsub G B\njnz G 2\nset F 0\nsub E -1\nset G E\nsub G B\njnz G -8\nsub D -1\nset G D\nsub G B\njnz G -13",
+];
+
+fn op(idiom: usize, b: &HashMap<char, char>) -> Op {
+    if idiom < 4 {
+        Op::MulAdd { target: b[&'A'], factor: b[&'B'], counter: b[&'C'] }
+    } else {
+        Op::Composite { n: b[&'B'], flag: b[&'F'], outer: b[&'D'], inner: b[&'E'], scratch: b[&'G'] }
+    }
+}
+
+fn bind(bindings: &mut HashMap<char, char>, placeholder: char, register: char) -> bool {
+    if let Some(r) = bindings.get(&placeholder) {
+        return *r == register;
+    }
+
+    if bindings.values().any(|r| *r == register) {
+        return false;
+    }
+
+    bindings.insert(placeholder, register);
+    true
+}
+
+fn unify_param(bindings: &mut HashMap<char, char>, pattern: &Param, actual: &Param) -> bool {
+    use Param::*;
+
+    match (pattern, actual) {
+        (Register(p), Register(r)) => bind(bindings, *p, *r),
+        (Value(p), Value(v)) => p == v,
+        _ => false,
+    }
+}
+
+fn unify(bindings: &mut HashMap<char, char>, pattern: &Instruction, actual: &Instruction) -> bool {
+    use Instruction::*;
+
+    match (pattern, actual) {
+        (Snd(p), Snd(a)) => unify_param(bindings, p, a),
+        (Rcv(p), Rcv(a)) => bind(bindings, *p, *a),
+        (Set((p, q)), Set((a, b)))
+        | (Add((p, q)), Add((a, b)))
+        | (Sub((p, q)), Sub((a, b)))
+        | (Mul((p, q)), Mul((a, b)))
+        | (Mod((p, q)), Mod((a, b))) => bind(bindings, *p, *a) && unify_param(bindings, q, b),
+        (Jgz((p, q)), Jgz((a, b)))
+        | (Jnz((p, q)), Jnz((a, b))) => unify_param(bindings, p, a) && unify_param(bindings, q, b),
+        _ => false,
+    }
+}
+
+fn matches(pattern: &[Instruction], program: &[Instruction]) -> Option<HashMap<char, char>> {
+    if program.len() < pattern.len() {
+        return None;
+    }
+
+    let mut bindings = HashMap::new();
+    pattern.iter()
+        .zip(program)
+        .all(|(p, a)| unify(&mut bindings, p, a))
+        .then_some(bindings)
+}
+
+// All jumps in the program as pairs of source and target. None if the
+// program contains a jump with a computed offset.
+fn jumps(program: &[Instruction]) -> Option<Vec<(isize, isize)>> {
+    use Instruction::*;
+
+    program.iter()
+        .enumerate()
+        .filter_map(|(i, instr)| match instr {
+            Jgz((_, offset)) | Jnz((_, offset)) => Some((i as isize, offset)),
+            _ => None,
+        })
+        .map(|(i, offset)| match offset {
+            Param::Value(v) => Some((i, i + v)),
+            Param::Register(_) => None,
+        })
+        .collect()
+}
+
+// Replaces known loop idioms with fused super-instructions. The fused
+// instruction takes the place of the first instruction of the loop and
+// jumps past it, all other instructions stay where they are so jump
+// offsets remain valid.
+pub fn optimize(program: &[Instruction]) -> Vec<Instruction> {
+    let mut result = program.to_vec();
+
+    let jumps = match jumps(program) {
+        Some(j) => j,
+        None => return result,
+    };
+
+    let idioms = IDIOMS.iter()
+        .map(|i| parse(i).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut i = 0;
+    while i < program.len() {
+        let found = idioms.iter()
+            .enumerate()
+            .find_map(|(n, pattern)| matches(pattern, &program[i..]).map(|b| (n, b, pattern.len())));
+
+        if let Some((n, bindings, length)) = found {
+            let start = i as isize;
+            let end = start + length as isize;
+
+            // The loop must only be entered through its first instruction.
+            let outside = |p: isize| p < start || p >= end;
+            let entered = jumps.iter().any(|(from, to)| outside(*from) && !outside(*to) && *to != start);
+
+            if !entered {
+                let fused = Fused { op: op(n, &bindings), length: length as isize, original: program[i].clone() };
+                result[i] = Instruction::Fused(Box::new(fused));
+                i += length;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn fuses_multiplication_loop() {
+        let program = parse("set b 3\nset c 4\nadd a b\nsub c 1\njnz c -2\nadd a 1").unwrap();
+        let optimized = optimize(&program);
+
+        assert_eq!("muladd a b c", optimized[2].to_string());
+
+        let mut machine = Machine::new(&optimized, ());
+        machine.run();
+
+        assert_eq!(13, machine.get('a'));
+        assert_eq!(0, machine.get('c'));
+    }
+
+    #[test]
+    pub fn falls_back_if_preconditions_fail() {
+        let program = parse("add a b\nsub c 1\njnz c -2").unwrap();
+        let optimized = optimize(&program);
+
+        let mut machine = Machine::new(&optimized, ());
+        machine.registers.insert('b', 2);
+
+        if let Instruction::Fused(fused) = &optimized[0] {
+            assert!(!fused.apply(&mut machine));
+            assert_eq!(0, machine.get('a'));
+        } else {
+            panic!("Loop was not fused");
+        }
+    }
+
+    #[test]
+    pub fn keeps_loops_that_are_jumped_into() {
+        let program = parse("jnz 1 2\nadd a b\nsub c 1\njnz c -2").unwrap();
+
+        assert_eq!(program, optimize(&program));
+    }
+
+    #[test]
+    pub fn fused_composite_matches_interpreter() {
+        let program = parse(include_str!("../day23/input")).unwrap();
+
+        let optimized = optimize(&program);
+        assert!(matches!(optimized[9], Instruction::Fused(_)));
+
+        let mut slow = Machine::new(&program, ());
+        let mut fast = Machine::new(&optimized, ());
+        slow.run();
+        fast.run();
+
+        assert_eq!(slow.registers, fast.registers);
+        assert_eq!(slow.muls, fast.muls);
+    }
+}
@@ -0,0 +1,272 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use crate::utils::{Budget, Exhausted};
+use super::{Instruction, Io, Machine, Receive, Snapshot, State};
+
+// A FIFO channel between programs. Unbounded if `capacity` is None.
+#[derive(Debug, Default)]
+pub struct Queue {
+    pub values: VecDeque<isize>,
+    pub capacity: Option<usize>,
+}
+
+impl Queue {
+    fn is_full(&self) -> bool {
+        self.capacity.map(|c| self.values.len() >= c).unwrap_or(false)
+    }
+}
+
+type Channel = Rc<RefCell<Queue>>;
+
+// `snd` and `rcv` of a program in a network. `snd` goes to every output
+// channel and blocks while any of them is full, `rcv` takes from the
+// first input channel that has a value. A program without an output
+// channel discards what it sends, a program without an input channel
+// blocks on every `rcv`.
+#[derive(Debug, Default)]
+pub struct Endpoint {
+    inputs: Vec<Channel>,
+    outputs: Vec<Channel>,
+    pub sent: usize,
+    pub received: usize,
+}
+
+fn attach(channels: &mut Vec<Channel>, channel: &Channel) {
+    if !channels.iter().any(|c| Rc::ptr_eq(c, channel)) {
+        channels.push(Rc::clone(channel));
+    }
+}
+
+impl Io for Endpoint {
+    fn snd(&mut self, value: isize) -> bool {
+        if self.outputs.iter().any(|o| o.borrow().is_full()) {
+            return false;
+        }
+
+        for output in &self.outputs {
+            output.borrow_mut().values.push_back(value);
+        }
+
+        self.sent += 1;
+        true
+    }
+
+    fn rcv(&mut self, _current: isize) -> Receive {
+        let value = self.inputs.iter().find_map(|i| i.borrow_mut().values.pop_front());
+
+        match value {
+            Some(v) => {
+                self.received += 1;
+                Receive::Value(v)
+            },
+            None => Receive::Block,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    // All programs terminated.
+    Halted,
+    // None of the programs can make progress, the given ones are blocked.
+    Deadlock(Vec<usize>),
+}
+
+#[derive(Debug, Default)]
+pub struct Network {
+    pub programs: Vec<Machine<Endpoint>>,
+    states: Vec<State>,
+    channels: Vec<Channel>,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Network::default()
+    }
+
+    // Creates n copies of a program, each with its id in register `p`,
+    // where program i sends to program i + 1 and the last one to the
+    // first one.
+    pub fn ring(instructions: &[Instruction], n: usize, capacity: Option<usize>) -> Self {
+        let mut network = Network::new();
+
+        for id in 0..n {
+            network.add_program(instructions, &[('p', id as isize)]);
+        }
+
+        for id in 0..n {
+            let channel = network.add_channel(capacity);
+            network.connect(id, (id + 1) % n, channel);
+        }
+
+        network
+    }
+
+    pub fn add_program(&mut self, instructions: &[Instruction], registers: &[(char, isize)]) -> usize {
+        let mut machine = Machine::new(instructions, Endpoint::default());
+        machine.registers.extend(registers.iter().cloned());
+
+        self.programs.push(machine);
+        self.states.push(State::Running);
+        self.programs.len() - 1
+    }
+
+    pub fn add_channel(&mut self, capacity: Option<usize>) -> usize {
+        let queue = Queue { values: VecDeque::new(), capacity };
+
+        self.channels.push(Rc::new(RefCell::new(queue)));
+        self.channels.len() - 1
+    }
+
+    // Lets program `from` send to program `to` through the given
+    // channel. Programs may have several input and output channels and
+    // several programs may share a channel.
+    pub fn connect(&mut self, from: usize, to: usize, channel: usize) {
+        let channel = &self.channels[channel];
+
+        attach(&mut self.programs[from].io.outputs, channel);
+        attach(&mut self.programs[to].io.inputs, channel);
+    }
+
+    pub fn channel(&self, channel: usize) -> Vec<isize> {
+        self.channels[channel].borrow().values.iter().cloned().collect()
+    }
+
+    pub fn states(&self) -> &[State] {
+        &self.states
+    }
+
+    // Runs the programs round robin, each one until it blocks or halts,
+    // until all of them have halted or none can make progress anymore.
+    pub fn run(&mut self) -> Outcome {
+        match self.run_within(&mut Budget::unlimited()) {
+            Ok(outcome) => outcome,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    // Like `run`, but shares the budget between all programs. The error
+    // carries the state of all programs.
+    pub fn run_within(&mut self, budget: &mut Budget) -> Result<Outcome, Exhausted<Vec<Snapshot>>> {
+        loop {
+            let mut progress = false;
+            let mut blocked = vec![];
+
+            for id in 0..self.programs.len() {
+                let steps = self.programs[id].steps;
+
+                self.states[id] = self.programs[id].run_within(budget)
+                    .map_err(|e| Exhausted {
+                        steps: e.steps,
+                        state: self.programs.iter().map(|p| p.snapshot()).collect(),
+                    })?;
+
+                let program = &self.programs[id];
+                if self.states[id] == State::Blocked {
+                    blocked.push(id);
+                }
+
+                progress |= program.steps != steps;
+            }
+
+            if blocked.is_empty() {
+                return Ok(Outcome::Halted);
+            }
+
+            if !progress {
+                return Ok(Outcome::Deadlock(blocked));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vm::parse;
+
+    #[test]
+    pub fn ring_of_three() {
+        let program = parse("snd p\nrcv a\nsnd a\nrcv b").unwrap();
+        let mut network = Network::ring(&program, 3, None);
+
+        assert_eq!(Outcome::Halted, network.run());
+        assert_eq!(2, network.programs[0].get('a'));
+        assert_eq!(1, network.programs[0].get('b'));
+        assert_eq!(0, network.programs[1].get('a'));
+        assert_eq!(&[State::Halted; 3], network.states());
+    }
+
+    #[test]
+    pub fn deadlock() {
+        let program = parse("rcv a\nsnd a").unwrap();
+        let mut network = Network::ring(&program, 2, None);
+
+        assert_eq!(Outcome::Deadlock(vec![0, 1]), network.run());
+        assert_eq!(&[State::Blocked; 2], network.states());
+    }
+
+    #[test]
+    pub fn fan_out_and_fan_in() {
+        // the source sends to both workers, which double and triple the
+        // values and both send to the sink
+        let mut network = Network::new();
+        let source = network.add_program(&parse("snd 1\nsnd 2").unwrap(), &[]);
+        let double = network.add_program(&parse("rcv a\nmul a 2\nsnd a\nrcv a\nmul a 2\nsnd a").unwrap(), &[]);
+        let triple = network.add_program(&parse("rcv a\nmul a 3\nsnd a\nrcv a\nmul a 3\nsnd a").unwrap(), &[]);
+        let sink = network.add_program(&parse("rcv a\nrcv b\nrcv c\nrcv d\nrcv e").unwrap(), &[]);
+
+        let to_double = network.add_channel(None);
+        let to_triple = network.add_channel(None);
+        let from_double = network.add_channel(None);
+        let from_triple = network.add_channel(None);
+        network.connect(source, double, to_double);
+        network.connect(source, triple, to_triple);
+        network.connect(double, sink, from_double);
+        network.connect(triple, sink, from_triple);
+
+        assert_eq!(Outcome::Deadlock(vec![sink]), network.run());
+        assert_eq!(&[State::Halted, State::Halted, State::Halted, State::Blocked], network.states());
+
+        let mut received = "abcd".chars().map(|r| network.programs[sink].get(r)).collect::<Vec<_>>();
+        received.sort();
+        assert_eq!(vec![2, 3, 4, 6], received);
+        assert_eq!(2, network.programs[source].io.sent);
+        assert_eq!(4, network.programs[sink].io.received);
+    }
+
+    #[test]
+    pub fn full_output_blocks_every_output() {
+        // the second value only goes out once the bounded channel is read,
+        // connecting the same channel twice attaches it once
+        let mut network = Network::new();
+        let sender = network.add_program(&parse("snd 1\nsnd 2").unwrap(), &[]);
+        let small = network.add_program(&parse("rcv a").unwrap(), &[]);
+        let large = network.add_program(&parse("rcv a\nrcv b\nrcv c").unwrap(), &[]);
+        let bounded = network.add_channel(Some(1));
+        let unbounded = network.add_channel(None);
+        network.connect(sender, small, bounded);
+        network.connect(sender, large, unbounded);
+        network.connect(sender, large, unbounded);
+
+        assert_eq!(Outcome::Deadlock(vec![large]), network.run());
+        assert_eq!(vec![2], network.channel(bounded));
+        assert_eq!(2, network.programs[large].get('b'));
+        assert_eq!(2, network.programs[sender].io.sent);
+    }
+
+    #[test]
+    pub fn bounded_channel_blocks_sender() {
+        let mut network = Network::new();
+        let sender = network.add_program(&parse("snd 1\nsnd 2\nsnd 3").unwrap(), &[]);
+        let receiver = network.add_program(&parse("rcv a").unwrap(), &[]);
+        let channel = network.add_channel(Some(1));
+        network.connect(sender, receiver, channel);
+
+        assert_eq!(Outcome::Deadlock(vec![sender]), network.run());
+        assert_eq!(&[State::Blocked, State::Halted], network.states());
+        assert_eq!(vec![2], network.channel(channel));
+        assert_eq!(1, network.programs[receiver].get('a'));
+    }
+}